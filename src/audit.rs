@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Audit log of login attempts
+//!
+//! Each login attempt is appended to the log file as a JSON object on its own line.
+
+use std::io::Result as IOResult;
+use std::path::Path;
+
+use jiff::Timestamp;
+use serde::Serialize;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+/// A single login attempt in the audit log
+#[derive(Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub username: Option<String>,
+    pub session: Option<String>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+}
+
+impl AuditRecord {
+    /// Create a record of a login attempt happening now.
+    pub fn new(
+        username: Option<String>,
+        session: Option<String>,
+        failure_reason: Option<String>,
+    ) -> Self {
+        Self {
+            timestamp: Timestamp::now().to_string(),
+            username,
+            session,
+            success: failure_reason.is_none(),
+            failure_reason,
+        }
+    }
+}
+
+/// Append-only log file of login attempts, which is kept open
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Open the log file at the given path for appending, creating it if needed.
+    pub async fn open(path: &Path) -> IOResult<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append the record to the log file, waiting till it's written.
+    pub async fn record(&self, record: &AuditRecord) {
+        let mut line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(err) => {
+                error!("Couldn't serialize audit record: {err}");
+                return;
+            }
+        };
+        line.push('\n');
+
+        let mut file = self.file.lock().await;
+        if let Err(err) = file.write_all(line.as_bytes()).await {
+            error!("Couldn't write to audit log: {err}");
+        } else if let Err(err) = file.flush().await {
+            error!("Couldn't flush audit log: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, remove_file};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("regreet-audit-{}.log", std::process::id()));
+        std::fs::write(&path, "{\"existing\":true}\n").unwrap();
+
+        let log = AuditLog::open(&path).await.unwrap();
+        log.record(&AuditRecord {
+            timestamp: "2024-01-01T00:00:00Z".to_string(),
+            username: Some("jdoe".to_string()),
+            session: Some("Sway".to_string()),
+            success: true,
+            failure_reason: None,
+        })
+        .await;
+        log.record(&AuditRecord {
+            timestamp: "2024-01-01T00:00:01Z".to_string(),
+            username: Some("jdoe".to_string()),
+            session: None,
+            success: false,
+            failure_reason: Some("Login failed".to_string()),
+        })
+        .await;
+
+        let text = read_to_string(&path).unwrap();
+        remove_file(&path).unwrap();
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                r#"{"existing":true}"#,
+                r#"{"timestamp":"2024-01-01T00:00:00Z","username":"jdoe","session":"Sway","success":true}"#,
+                r#"{"timestamp":"2024-01-01T00:00:01Z","username":"jdoe","session":null,"success":false,"failure_reason":"Login failed"}"#,
+            ]
+        );
+    }
+}
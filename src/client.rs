@@ -5,13 +5,19 @@
 //! Client that communicates with greetd
 
 use std::env;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::io::Result as IOResult;
+use std::time::Duration;
 
 use greetd_ipc::{
     codec::{Error as GreetdError, TokioCodec},
     AuthMessageType, ErrorType, Request, Response,
 };
-use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    time::{error::Elapsed, timeout},
+};
 
 /// Environment variable containing the path to the greetd socket
 const GREETD_SOCK_ENV_VAR: &str = "GREETD_SOCK";
@@ -33,17 +39,55 @@ pub enum AuthStatus {
     Done,
 }
 
+impl Display for AuthStatus {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::NotStarted => write!(f, "not started"),
+            Self::InProgress => write!(f, "in progress"),
+            Self::Done => write!(f, "done"),
+        }
+    }
+}
+
 /// Client that uses UNIX sockets to communicate with greetd
 pub struct GreetdClient {
-    /// Socket to communicate with greetd
+    /// Whether to fake the communication with greetd, for demo purposes
+    demo: bool,
+    /// Socket to communicate with greetd, which is `None` in demo mode or if it has to reconnect
     socket: Option<UnixStream>,
     /// Current authentication status
     auth_status: AuthStatus,
+    /// Maximum time to wait for greetd to respond to a request
+    timeout: Duration,
+}
+
+/// Send a request to greetd and read its response, giving up if this takes longer than `limit`.
+async fn send_request<S>(
+    socket: &mut S,
+    msg: Request,
+    limit: Duration,
+) -> Result<GreetdResult, Elapsed>
+where
+    S: AsyncReadExt + AsyncWriteExt + Unpin + Send,
+{
+    let exchange = async {
+        msg.write_to(socket).await?;
+        Response::read_from(socket).await
+    };
+    timeout(limit, exchange).await
+}
+
+/// Connect to the socket of greetd.
+async fn connect() -> IOResult<UnixStream> {
+    let sock_path = env::var(GREETD_SOCK_ENV_VAR).unwrap_or_else(|_| {
+        panic!("Missing environment variable '{GREETD_SOCK_ENV_VAR}'. Is greetd running?",)
+    });
+    UnixStream::connect(sock_path).await
 }
 
 impl GreetdClient {
     /// Initialize the socket to communicate with greetd.
-    pub async fn new(demo: bool) -> IOResult<Self> {
+    pub async fn new(demo: bool, timeout: Duration) -> IOResult<Self> {
         let socket: Option<UnixStream> = if demo {
             warn!(
                 "Run as demo: [otp: {}, password: {}]",
@@ -51,28 +95,57 @@ impl GreetdClient {
             );
             None
         } else {
-            let sock_path = env::var(GREETD_SOCK_ENV_VAR).unwrap_or_else(|_| {
-                panic!("Missing environment variable '{GREETD_SOCK_ENV_VAR}'. Is greetd running?",)
-            });
-            Some(UnixStream::connect(sock_path).await?)
+            Some(connect().await?)
         };
 
         Ok(Self {
+            demo,
             socket,
             auth_status: AuthStatus::NotStarted,
+            timeout,
         })
     }
 
+    /// Send a request to greetd and read its response, reconnecting first if needed.
+    ///
+    /// If greetd doesn't respond in time, the socket is dropped, since it may have a partly sent
+    /// request or an unread response. A new one is then connected for the next request, which
+    /// greetd treats as a new greeter without a session.
+    async fn request(&mut self, msg: Request) -> GreetdResult {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            None => {
+                info!("Reconnecting to greetd");
+                let socket = connect()
+                    .await
+                    .map_err(|err| GreetdError::Io(format!("couldn't reconnect: {err}")))?;
+                self.socket.insert(socket)
+            }
+        };
+
+        match send_request(socket, msg, self.timeout).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("greetd didn't respond in time; dropping the connection");
+                self.socket = None;
+                self.auth_status = AuthStatus::NotStarted;
+                Err(GreetdError::Io(format!(
+                    "timed out after {:?} waiting for greetd",
+                    self.timeout
+                )))
+            }
+        }
+    }
+
     /// Initialize a greetd session.
     pub async fn create_session(&mut self, username: &str) -> GreetdResult {
         info!("Creating session for username: {username}");
 
-        let resp: Response = if let Some(socket) = &mut self.socket {
+        let resp: Response = if !self.demo {
             let msg = Request::CreateSession {
                 username: username.to_string(),
             };
-            msg.write_to(socket).await?;
-            Response::read_from(socket).await?
+            self.request(msg).await?
         } else {
             Response::AuthMessage {
                 auth_message_type: AuthMessageType::Secret,
@@ -98,10 +171,9 @@ impl GreetdClient {
     pub async fn send_auth_response(&mut self, input: Option<String>) -> GreetdResult {
         info!("Sending password to greetd");
 
-        let resp: Response = if let Some(socket) = &mut self.socket {
+        let resp: Response = if !self.demo {
             let msg = Request::PostAuthMessageResponse { response: input };
-            msg.write_to(socket).await?;
-            Response::read_from(socket).await?
+            self.request(msg).await?
         } else {
             match input.as_deref() {
                 Some(DEMO_OTP) => Response::AuthMessage {
@@ -140,18 +212,15 @@ impl GreetdClient {
     ) -> GreetdResult {
         info!("Starting greetd session with command: {command:?}");
 
-        if self.socket.is_none() {
+        if self.demo {
             return Ok(Response::Success);
         }
 
-        let socket = self.socket.as_mut().unwrap();
         let msg = Request::StartSession {
             cmd: command,
             env: environment,
         };
-        msg.write_to(socket).await?;
-
-        let resp = Response::read_from(socket).await?;
+        let resp = self.request(msg).await?;
         if let Response::AuthMessage { .. } = resp {
             unimplemented!("greetd responded with auth request after requesting session start.");
         }
@@ -163,15 +232,11 @@ impl GreetdClient {
         info!("Cancelling greetd session");
         self.auth_status = AuthStatus::NotStarted;
 
-        if self.socket.is_none() {
+        if self.demo {
             return Ok(Response::Success);
         }
 
-        let socket = self.socket.as_mut().unwrap();
-        let msg = Request::CancelSession;
-        msg.write_to(socket).await?;
-
-        let resp = Response::read_from(socket).await?;
+        let resp = self.request(Request::CancelSession).await?;
         if let Response::AuthMessage { .. } = resp {
             unimplemented!(
                 "greetd responded with auth request after requesting session cancellation."
@@ -184,3 +249,45 @@ impl GreetdClient {
         &self.auth_status
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(AuthStatus::NotStarted => "not started"; "not started")]
+    #[test_case(AuthStatus::InProgress => "in progress"; "in progress")]
+    #[test_case(AuthStatus::Done => "done"; "done")]
+    fn auth_status_display(status: AuthStatus) -> String {
+        status.to_string()
+    }
+
+    #[tokio::test]
+    async fn stalled_greetd_times_out() {
+        // Keep the other end alive, but never respond through it.
+        let (mut socket, _greetd) = UnixStream::pair().expect("Couldn't create socket pair");
+        let limit = Duration::from_millis(50);
+
+        let start = std::time::Instant::now();
+        let result = send_request(&mut socket, Request::CancelSession, limit).await;
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < limit * 10);
+    }
+
+    #[tokio::test]
+    async fn timeout_drops_socket() {
+        let (socket, _greetd) = UnixStream::pair().expect("Couldn't create socket pair");
+        let mut client = GreetdClient {
+            demo: false,
+            socket: Some(socket),
+            auth_status: AuthStatus::InProgress,
+            timeout: Duration::from_millis(50),
+        };
+
+        let result = client.send_auth_response(None).await;
+
+        assert!(matches!(result, Err(GreetdError::Io(_))));
+        assert!(client.socket.is_none());
+        assert!(matches!(client.get_auth_status(), AuthStatus::NotStarted));
+    }
+}
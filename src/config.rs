@@ -4,25 +4,177 @@
 
 //! Configuration for the greeter
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
+use regex::RegexBuilder;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
-use crate::constants::{GREETING_MSG, POWEROFF_CMD, REBOOT_CMD, X11_CMD_PREFIX};
+use crate::constants::{GREETING_MSG, NO_SESSION_MSG, POWEROFF_CMD, REBOOT_CMD, X11_CMD_PREFIX};
+use crate::gui::widget::battery::BatteryConfig;
 use crate::gui::widget::clock::ClockConfig;
-use crate::tomlutils::load_toml;
+use crate::gui::widget::network::NetworkConfig;
+use crate::tomlutils::load_toml_with_includes;
 
 #[derive(Deserialize, Serialize)]
 pub struct AppearanceSettings {
     #[serde(default = "default_greeting_msg")]
     pub greeting_msg: String,
+    /// Whether to show the description of the selected session as a tooltip
+    #[serde(default = "default_show_session_tooltip")]
+    pub show_session_tooltip: bool,
+    /// Whether to show a strength indicator for visible inputs (eg. when setting passwords)
+    #[serde(default)]
+    pub show_password_strength: bool,
+    /// Margin between the login panel and the top edge of the screen
+    #[serde(default)]
+    pub panel_margin_top: Option<i32>,
+    /// Margin between the login panel and the bottom edge of the screen
+    #[serde(default)]
+    pub panel_margin_bottom: Option<i32>,
+    /// Margin between the login panel and the starting (usually left) edge of the screen
+    #[serde(default)]
+    pub panel_margin_start: Option<i32>,
+    /// Margin between the login panel and the ending (usually right) edge of the screen
+    #[serde(default)]
+    pub panel_margin_end: Option<i32>,
+    /// Position of the login panel on the screen
+    #[serde(default)]
+    pub login_box: LoginBoxPosition,
+    /// Duration (in milliseconds) of the fade-in animation on startup, with 0 disabling it
+    #[serde(default)]
+    pub fade_in_ms: u64,
+    /// Order of the user and session rows in the login panel
+    #[serde(default)]
+    pub widget_order: WidgetOrder,
+    /// Alignment of the message shown at the top of the login panel
+    #[serde(default)]
+    pub message_align: MessageAlign,
+    /// Tooltip for the button to manually enter the username
+    #[serde(default)]
+    pub manual_user_tooltip: Option<String>,
+    /// Tooltip for the button to manually enter the session command
+    #[serde(default)]
+    pub manual_session_tooltip: Option<String>,
+    /// Tooltip for the login button
+    #[serde(default)]
+    pub login_tooltip: Option<String>,
+    /// Total number of authentication steps (ie. prompts for input), shown along with the current
+    /// step
+    #[serde(default, alias = "expected_auth_steps")]
+    pub auth_steps: Option<usize>,
+    /// Time (in seconds) without any input after which a login attempt is cancelled
+    #[serde(default)]
+    pub auth_timeout_secs: Option<u64>,
+    /// Time (in seconds) after which an error is cleared, with 0 meaning it is never cleared
+    #[serde(default = "default_error_timeout_secs")]
+    pub error_timeout_secs: u64,
+    /// Time (in milliseconds) to wait before showing an error, so that only the last of several
+    /// rapid errors is shown
+    #[serde(default)]
+    pub error_batch_delay_ms: u64,
+    /// Message shown when neither a session nor a login shell is found for the user
+    #[serde(default)]
+    pub no_session_message: Option<String>,
+    /// Minimum width (in pixels) of the users combo box, with 0 meaning no minimum
+    #[serde(default)]
+    pub usernames_min_width: u32,
+    /// Minimum width (in pixels) of the sessions combo box, with 0 meaning no minimum
+    #[serde(default)]
+    pub sessions_min_width: u32,
+    /// Whether to show the hostname in the top panel, unless the greeting message already has it
+    #[serde(default)]
+    pub show_hostname: bool,
+    /// File whose contents are shown below the greeting message (eg. `/etc/motd` or `/etc/issue`)
+    #[serde(default)]
+    pub motd_path: Option<PathBuf>,
+    /// Icons shown next to info messages from PAM (eg. asking for a fingerprint), keyed by regex
+    /// patterns matching the messages
+    #[serde(default)]
+    pub pam_hints: BTreeMap<String, String>,
 }
 
 impl Default for AppearanceSettings {
     fn default() -> Self {
         AppearanceSettings {
             greeting_msg: default_greeting_msg(),
+            show_session_tooltip: default_show_session_tooltip(),
+            show_password_strength: false,
+            panel_margin_top: None,
+            panel_margin_bottom: None,
+            panel_margin_start: None,
+            panel_margin_end: None,
+            login_box: LoginBoxPosition::default(),
+            fade_in_ms: 0,
+            widget_order: WidgetOrder::default(),
+            message_align: MessageAlign::default(),
+            manual_user_tooltip: None,
+            manual_session_tooltip: None,
+            login_tooltip: None,
+            auth_steps: None,
+            auth_timeout_secs: None,
+            error_timeout_secs: default_error_timeout_secs(),
+            error_batch_delay_ms: 0,
+            no_session_message: None,
+            usernames_min_width: 0,
+            sessions_min_width: 0,
+            show_hostname: false,
+            motd_path: None,
+            pam_hints: BTreeMap::new(),
+        }
+    }
+}
+
+/// Alignment of the login panel along one direction of the screen
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PanelAlign {
+    Start,
+    #[default]
+    Center,
+    End,
+}
+
+/// Position of the login panel on the screen
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LoginBoxPosition {
+    /// Horizontal alignment of the login panel
+    #[serde(default)]
+    pub halign: PanelAlign,
+    /// Vertical alignment of the login panel
+    #[serde(default)]
+    pub valign: PanelAlign,
+}
+
+/// Alignment of a text label
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// Order of the user and session rows in the login panel
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetOrder {
+    #[default]
+    UserSession,
+    SessionUser,
+}
+
+impl WidgetOrder {
+    /// Get the rows in the login panel's grid for the user and session widgets respectively.
+    ///
+    /// The authentication input widgets replace the session widgets, so they share the session row.
+    pub fn rows(self) -> (i32, i32) {
+        match self {
+            Self::UserSession => (1, 2),
+            Self::SessionUser => (2, 1),
         }
     }
 }
@@ -34,14 +186,41 @@ pub struct GtkSettings {
     pub application_prefer_dark_theme: bool,
     #[serde(default)]
     pub cursor_theme_name: Option<String>,
+    /// Size of the cursor in pixels
+    #[serde(default)]
+    pub cursor_size: Option<u32>,
     #[serde(default)]
     pub font_name: Option<String>,
+    /// Factor by which to scale all text
+    #[serde(default)]
+    pub text_scaling_factor: Option<f64>,
     #[serde(default)]
     pub icon_theme_name: Option<String>,
+    /// Icon themes to try in order, after `icon_theme_name`, until one is installed
+    #[serde(default)]
+    pub icon_theme_names: Vec<String>,
     #[serde(default)]
     pub theme_name: Option<String>,
 }
 
+impl GtkSettings {
+    /// Get the font resolution (in 1024ths of a DPI) that scales text by the configured factor.
+    ///
+    /// GTK4 has no setting for text scaling, so this scales the default resolution of 96 DPI.
+    pub fn xft_dpi(&self) -> Option<i32> {
+        self.text_scaling_factor
+            .map(|factor| (96.0 * 1024.0 * factor).round() as i32)
+    }
+
+    /// Get all configured icon theme names, in order of preference.
+    pub fn icon_theme_candidates(&self) -> impl Iterator<Item = &str> {
+        self.icon_theme_name
+            .iter()
+            .chain(self.icon_theme_names.iter())
+            .map(String::as_str)
+    }
+}
+
 /// Analogue to `gtk4::ContentFit`
 #[derive(Default, Deserialize, Serialize)]
 pub enum BgFit {
@@ -53,12 +232,64 @@ pub enum BgFit {
 }
 
 /// Struct for info about the background image
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
 struct Background {
     #[serde(default)]
     path: Option<String>,
+    /// Background images shown one after the other after `path`
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Time for which each background image is shown, if there are multiple
+    #[serde(
+        serialize_with = "humantime_serde::serialize",
+        deserialize_with = "deserialize_nonzero_duration",
+        default = "default_background_interval"
+    )]
+    interval: Duration,
+    /// Background image used instead of `path` when the dark theme is preferred
+    #[serde(default)]
+    dark_path: Option<String>,
     #[serde(default)]
     fit: BgFit,
+    /// Background images for specific monitors, used instead of the above ones
+    #[serde(default)]
+    monitors: Vec<MonitorBackground>,
+}
+
+/// Background image for a specific monitor
+#[derive(Deserialize, Serialize)]
+struct MonitorBackground {
+    /// Name of the monitor's connector, such as "DP-1" or "HDMI-A-1"
+    connector: String,
+    path: String,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background {
+            path: None,
+            paths: Vec::new(),
+            interval: default_background_interval(),
+            dark_path: None,
+            fit: BgFit::default(),
+            monitors: Vec::new(),
+        }
+    }
+}
+
+const fn default_background_interval() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Deserialize a human-readable duration (eg. "5m"), rejecting zero.
+fn deserialize_nonzero_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let duration: Duration = humantime_serde::deserialize(deserializer)?;
+    if duration.is_zero() {
+        return Err(D::Error::custom("duration must be greater than 0"));
+    }
+    Ok(duration)
 }
 
 /// Struct for various system commands
@@ -70,6 +301,18 @@ pub struct SystemCommands {
     pub poweroff: Vec<String>,
     #[serde(default = "default_x11_command_prefix")]
     pub x11_prefix: Vec<String>,
+    /// Maximum time in seconds to wait for greetd to respond to a request
+    #[serde(default = "default_greetd_timeout_secs")]
+    pub greetd_timeout_secs: NonZeroU64,
+    /// Whether to create the user's runtime directory (`XDG_RUNTIME_DIR`) before starting a session
+    #[serde(default)]
+    pub create_runtime_dir: bool,
+    /// Command to run after authentication but before starting the session
+    #[serde(default)]
+    pub pre_session: Option<Vec<String>>,
+    /// Command to run an on-screen keyboard when an authentication input is touched
+    #[serde(default)]
+    pub osk: Option<Vec<String>>,
 }
 
 impl Default for SystemCommands {
@@ -78,10 +321,204 @@ impl Default for SystemCommands {
             reboot: default_reboot_command(),
             poweroff: default_poweroff_command(),
             x11_prefix: default_x11_command_prefix(),
+            greetd_timeout_secs: default_greetd_timeout_secs(),
+            create_runtime_dir: false,
+            pre_session: None,
+            osk: None,
+        }
+    }
+}
+
+/// Struct for settings related to logging and error reporting
+#[derive(Default, Deserialize, Serialize)]
+pub struct LoggingSettings {
+    /// Whether to show the type of errors from greetd along with their descriptions
+    #[serde(default)]
+    pub verbose_errors: bool,
+    /// Path to the file where login attempts are logged
+    #[serde(default)]
+    pub audit_path: Option<PathBuf>,
+}
+
+/// Struct for settings related to protecting against password guessing
+#[derive(Deserialize, Serialize)]
+pub struct SecuritySettings {
+    /// Number of consecutive failed login attempts after which a user is locked out
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Time (in seconds) for which a user is locked out
+    #[serde(default = "default_lockout_secs")]
+    pub lockout_secs: u64,
+    /// Messages shown instead of errors from PAM, keyed by regex patterns matching the errors
+    #[serde(default = "default_pam_error_map")]
+    pub pam_error_map: BTreeMap<String, String>,
+}
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        SecuritySettings {
+            max_attempts: None,
+            lockout_secs: default_lockout_secs(),
+            pam_error_map: default_pam_error_map(),
+        }
+    }
+}
+
+const fn default_lockout_secs() -> u64 {
+    30
+}
+
+fn default_pam_error_map() -> BTreeMap<String, String> {
+    [
+        ("AUTH_ERR", "Incorrect credentials"),
+        ("USER_UNKNOWN", "Incorrect credentials"),
+        ("MAXTRIES", "Too many failed attempts — try again later"),
+        ("account locked", "Account is locked — try again later"),
+        (
+            "ACCT_EXPIRED",
+            "Account has expired — contact your administrator",
+        ),
+        ("PERM_DENIED", "Permission denied"),
+        (
+            "AUTHINFO_UNAVAIL",
+            "Authentication service is unavailable — contact your administrator",
+        ),
+    ]
+    .into_iter()
+    .map(|(pattern, message)| (pattern.to_string(), message.to_string()))
+    .collect()
+}
+
+/// Find the value of the regex pattern (matched case-insensitively) that matches earliest in the
+/// text, if any.
+fn find_by_pattern<'a>(patterns: &'a BTreeMap<String, String>, text: &str) -> Option<&'a str> {
+    patterns
+        .iter()
+        .filter_map(|(pattern, value)| {
+            match RegexBuilder::new(pattern).case_insensitive(true).build() {
+                Ok(regex) => regex.find(text).map(|found| (found.start(), value)),
+                Err(err) => {
+                    warn!("Invalid pattern '{pattern}': {err}");
+                    None
+                }
+            }
+        })
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Struct for settings related to automatically logging in the last user
+#[derive(Default, Deserialize, Serialize)]
+pub struct AutoLoginSettings {
+    /// Time (in seconds) without any input after which the last user is logged in
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Struct for settings related to finding users
+#[derive(Default, Deserialize, Serialize)]
+pub struct UserSettings {
+    /// System usernames of users to hide, even if their UIDs are in the range of regular users
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    /// Whether to show a search entry for filtering the users
+    #[serde(default)]
+    pub show_search: bool,
+}
+
+/// Struct for settings related to finding sessions
+#[derive(Deserialize, Serialize)]
+pub struct SessionSettings {
+    /// Names (without the `.desktop` extension) of session files to hide
+    #[serde(default)]
+    pub blacklisted: Vec<String>,
+    /// Whether to show whether each session is for X11 or Wayland
+    #[serde(default = "default_show_type_badge")]
+    pub show_type_badge: bool,
+    /// Whether to show a search entry for filtering the sessions
+    #[serde(default)]
+    pub show_search: bool,
+    /// Whether to group the sessions by their primary categories
+    #[serde(default)]
+    pub group_by_category: bool,
+    /// Order of the groups of sessions, when grouping them by category
+    #[serde(default = "default_category_order")]
+    pub category_order: Vec<String>,
+    /// Whether to also show the login shells as sessions
+    #[serde(default)]
+    pub show_shells: bool,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings {
+            blacklisted: Vec::new(),
+            show_type_badge: default_show_type_badge(),
+            show_search: false,
+            group_by_category: false,
+            category_order: default_category_order(),
+            show_shells: false,
+        }
+    }
+}
+
+fn default_category_order() -> Vec<String> {
+    ["Wayland", "X11", "Tiling", "Desktop"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+const fn default_show_type_badge() -> bool {
+    true
+}
+
+/// Struct for settings related to locking the greeter to a single user and session
+#[derive(Default, Deserialize, Serialize)]
+pub struct KioskSettings {
+    /// Whether to always log in the configured user into the configured session
+    #[serde(default)]
+    pub enabled: bool,
+    /// System username of the user to log in
+    #[serde(default)]
+    pub user: String,
+    /// Name of the session to start
+    #[serde(default)]
+    pub session: String,
+}
+
+/// Struct for settings related to logging in as a guest
+#[derive(Deserialize, Serialize)]
+pub struct GuestSettings {
+    /// Whether to show an entry for logging in as a guest among the users
+    #[serde(default)]
+    pub enabled: bool,
+    /// System username that the guest logs in as
+    #[serde(default = "default_guest_username")]
+    pub username: String,
+    /// Name of the session to select for the guest
+    #[serde(default)]
+    pub session: Option<String>,
+    /// Command to run before logging in as the guest (eg. to create a temporary home directory)
+    #[serde(default)]
+    pub pre_login_cmd: Option<Vec<String>>,
+}
+
+impl Default for GuestSettings {
+    fn default() -> Self {
+        GuestSettings {
+            enabled: false,
+            username: default_guest_username(),
+            session: None,
+            pre_login_cmd: None,
         }
     }
 }
 
+fn default_guest_username() -> String {
+    "guest".to_string()
+}
+
 fn default_reboot_command() -> Vec<String> {
     shlex::split(REBOOT_CMD).expect("Unable to lex reboot command")
 }
@@ -94,12 +531,36 @@ fn default_x11_command_prefix() -> Vec<String> {
     shlex::split(X11_CMD_PREFIX).expect("Unable to lex X11 command prefix")
 }
 
+fn default_greetd_timeout_secs() -> NonZeroU64 {
+    NonZeroU64::new(30).expect("Default greetd timeout should be non-zero")
+}
+
 fn default_greeting_msg() -> String {
     GREETING_MSG.to_string()
 }
 
+const fn default_show_session_tooltip() -> bool {
+    true
+}
+
+const fn default_error_timeout_secs() -> u64 {
+    5
+}
+
+/// Settings configured for a specific user
+#[derive(Deserialize, Serialize)]
+pub struct UserDefaults {
+    pub username: String,
+    /// Name of the session to select for this user, instead of the last used session
+    #[serde(default)]
+    pub default_session: Option<String>,
+    /// Message shown instead of the global greeting message when this user is selected
+    #[serde(default)]
+    pub greeting: Option<String>,
+}
+
 /// The configuration struct
-#[derive(Default, Deserialize)]
+#[derive(Default, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
     appearance: AppearanceSettings,
@@ -116,19 +577,49 @@ pub struct Config {
     #[serde(default)]
     commands: SystemCommands,
 
+    #[serde(default)]
+    logging: LoggingSettings,
+
+    #[serde(default)]
+    security: SecuritySettings,
+
+    #[serde(default)]
+    auto_login: AutoLoginSettings,
+
+    #[serde(default)]
+    users: UserSettings,
+
+    #[serde(default)]
+    sessions: SessionSettings,
+
+    #[serde(default)]
+    user_defaults: Vec<UserDefaults>,
+
+    #[serde(default)]
+    guest: GuestSettings,
+
+    #[serde(default)]
+    kiosk: KioskSettings,
+
     #[serde(default)]
     pub(crate) widget: WidgetConfig,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct WidgetConfig {
     #[serde(default)]
     pub(crate) clock: ClockConfig,
+
+    #[serde(default)]
+    pub(crate) battery: BatteryConfig,
+
+    #[serde(default)]
+    pub(crate) network: NetworkConfig,
 }
 
 impl Config {
     pub fn new(path: &Path) -> Self {
-        load_toml(path)
+        load_toml_with_includes(path)
     }
 
     pub fn get_env(&self) -> &HashMap<String, String> {
@@ -139,6 +630,37 @@ impl Config {
         self.background.path.as_deref()
     }
 
+    /// Get all background images, in the order that they're shown.
+    pub fn get_backgrounds(&self) -> impl Iterator<Item = &str> {
+        self.background
+            .path
+            .iter()
+            .chain(&self.background.paths)
+            .map(String::as_str)
+    }
+
+    /// Get the background images configured for specific monitors, along with their connectors.
+    pub fn get_monitor_backgrounds(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.background
+            .monitors
+            .iter()
+            .map(|monitor| (monitor.connector.as_str(), monitor.path.as_str()))
+    }
+
+    /// Get the background image configured for the monitor with the given connector, if any.
+    pub fn get_monitor_background(&self, connector: &str) -> Option<&str> {
+        self.get_monitor_backgrounds()
+            .find_map(|(name, path)| (name == connector).then_some(path))
+    }
+
+    pub fn get_background_interval(&self) -> Duration {
+        self.background.interval
+    }
+
+    pub fn get_dark_background(&self) -> Option<&str> {
+        self.background.dark_path.as_deref()
+    }
+
     #[cfg(feature = "gtk4_8")]
     pub fn get_background_fit(&self) -> &BgFit {
         &self.background.fit
@@ -155,4 +677,288 @@ impl Config {
     pub fn get_default_message(&self) -> String {
         self.appearance.greeting_msg.clone()
     }
+
+    pub fn get_no_session_message(&self) -> &str {
+        self.appearance
+            .no_session_message
+            .as_deref()
+            .unwrap_or(NO_SESSION_MSG)
+    }
+
+    pub fn get_appearance(&self) -> &AppearanceSettings {
+        &self.appearance
+    }
+
+    pub fn get_logging(&self) -> &LoggingSettings {
+        &self.logging
+    }
+
+    pub fn get_security(&self) -> &SecuritySettings {
+        &self.security
+    }
+
+    pub fn get_auto_login(&self) -> &AutoLoginSettings {
+        &self.auto_login
+    }
+
+    pub fn get_users(&self) -> &UserSettings {
+        &self.users
+    }
+
+    pub fn get_sessions(&self) -> &SessionSettings {
+        &self.sessions
+    }
+
+    pub fn get_guest(&self) -> &GuestSettings {
+        &self.guest
+    }
+
+    /// Get the kiosk settings, if kiosk mode is enabled.
+    pub fn get_kiosk(&self) -> Option<&KioskSettings> {
+        self.kiosk.enabled.then_some(&self.kiosk)
+    }
+
+    /// Check whether the given user is the guest, if logging in as a guest is enabled.
+    pub fn is_guest(&self, username: &str) -> bool {
+        self.guest.enabled && self.guest.username == username
+    }
+
+    /// Get the session configured to be selected for the given user, if any.
+    pub fn get_default_session(&self, username: &str) -> Option<&str> {
+        self.user_defaults
+            .iter()
+            .find(|defaults| defaults.username == username)
+            .and_then(|defaults| defaults.default_session.as_deref())
+            .or_else(|| {
+                self.is_guest(username)
+                    .then_some(self.guest.session.as_deref())
+                    .flatten()
+            })
+    }
+
+    /// Get the greeting message for the given user, falling back to the global one.
+    pub fn get_user_greeting(&self, username: &str) -> String {
+        self.user_defaults
+            .iter()
+            .find(|defaults| defaults.username == username)
+            .and_then(|defaults| defaults.greeting.clone())
+            .unwrap_or_else(|| self.get_default_message())
+    }
+
+    /// Get the name of the icon to show next to the given info message from PAM, if any.
+    pub fn get_pam_hint_icon(&self, message: &str) -> Option<&str> {
+        find_by_pattern(&self.appearance.pam_hints, message)
+    }
+
+    /// Get the user-friendly message to show instead of the given error from PAM, if any.
+    pub fn get_pam_error_message(&self, error: &str) -> Option<&str> {
+        find_by_pattern(&self.security.pam_error_map, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(WidgetOrder::UserSession => (1, 2); "user first")]
+    #[test_case(WidgetOrder::SessionUser => (2, 1); "session first")]
+    fn widget_order_rows(order: WidgetOrder) -> (i32, i32) {
+        order.rows()
+    }
+
+    #[test_case(None => None; "unset")]
+    #[test_case(Some(1.0) => Some(96 * 1024); "unscaled")]
+    #[test_case(Some(1.5) => Some(144 * 1024); "scaled up")]
+    fn xft_dpi(text_scaling_factor: Option<f64>) -> Option<i32> {
+        GtkSettings {
+            text_scaling_factor,
+            ..Default::default()
+        }
+        .xft_dpi()
+    }
+
+    #[test_case(
+        "Swipe your finger across the reader" => Some("fingerprint-symbolic".to_string());
+        "fingerprint"
+    )]
+    #[test_case("Enter the code from your app" => Some("phone-symbolic".to_string()); "totp")]
+    #[test_case("Password: " => None; "no match")]
+    fn pam_hint_icon(message: &str) -> Option<String> {
+        let config: Config = toml::from_str(
+            r#"
+            [appearance.pam_hints]
+            "fingerprint|swipe|finger" = "fingerprint-symbolic"
+            "code" = "phone-symbolic"
+            "[" = "invalid-symbolic"
+            "#,
+        )
+        .unwrap();
+        config.get_pam_hint_icon(message).map(str::to_string)
+    }
+
+    #[test_case("pam_authenticate: AUTH_ERR" => Some("Incorrect credentials".to_string()); "auth")]
+    #[test_case(
+        "Account locked due to 3 failed logins\npam_authenticate: AUTH_ERR"
+        => Some("Account is locked — try again later".to_string());
+        "earliest match"
+    )]
+    #[test_case("Something else went wrong" => None; "no match")]
+    fn pam_error_message(error: &str) -> Option<String> {
+        Config::default()
+            .get_pam_error_message(error)
+            .map(str::to_string)
+    }
+
+    #[test_case(true, "guest" => Some("Xfce".to_string()); "guest")]
+    #[test_case(false, "guest" => None; "disabled")]
+    #[test_case(true, "jdoe" => None; "other user")]
+    fn guest_session(enabled: bool, username: &str) -> Option<String> {
+        let config = Config {
+            guest: GuestSettings {
+                enabled,
+                session: Some("Xfce".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.get_default_session(username).map(str::to_string)
+    }
+
+    #[test]
+    fn tooltips_from_toml() {
+        let appearance: AppearanceSettings = toml::from_str(
+            r#"
+            manual_user_tooltip = "Benutzername eingeben"
+            login_tooltip = "Anmelden"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            appearance.manual_user_tooltip.as_deref(),
+            Some("Benutzername eingeben")
+        );
+        assert_eq!(appearance.manual_session_tooltip, None);
+        assert_eq!(appearance.login_tooltip.as_deref(), Some("Anmelden"));
+    }
+
+    #[test_case("teacher" => Some("GNOME".to_string()); "configured")]
+    #[test_case("student" => None; "no default session")]
+    #[test_case("guest" => None; "not configured")]
+    fn default_session(username: &str) -> Option<String> {
+        let config: Config = toml::from_str(
+            r#"
+            [[user_defaults]]
+            username = "teacher"
+            default_session = "GNOME"
+
+            [[user_defaults]]
+            username = "student"
+            "#,
+        )
+        .unwrap();
+        config.get_default_session(username).map(str::to_string)
+    }
+
+    #[test_case("teacher" => "Good morning, {username}!"; "configured")]
+    #[test_case("student" => "Welcome back!"; "not configured")]
+    fn user_greeting(username: &str) -> String {
+        let config: Config = toml::from_str(
+            r#"
+            [appearance]
+            greeting_msg = "Welcome back!"
+
+            [[user_defaults]]
+            username = "teacher"
+            greeting = "Good morning, {username}!"
+            "#,
+        )
+        .unwrap();
+        config.get_user_greeting(username)
+    }
+
+    #[test]
+    fn zero_greetd_timeout() {
+        let result = toml::from_str::<Config>("[commands]\ngreetd_timeout_secs = 0\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn backgrounds_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            [background]
+            path = "/first.jpg"
+            paths = ["/second.jpg", "/third.jpg"]
+            interval = "1m"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.get_backgrounds().collect::<Vec<_>>(),
+            ["/first.jpg", "/second.jpg", "/third.jpg"]
+        );
+        assert_eq!(config.get_background_interval(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn expected_auth_steps_alias() {
+        let config: Config = toml::from_str("[appearance]\nexpected_auth_steps = 3\n").unwrap();
+        assert_eq!(config.get_appearance().auth_steps, Some(3));
+    }
+
+    #[test]
+    fn zero_background_interval() {
+        let result = toml::from_str::<Config>("[background]\ninterval = \"0s\"\n");
+        assert!(result.is_err());
+    }
+
+    #[test_case("DP-1" => Some("/office.jpg".to_string()); "configured")]
+    #[test_case("HDMI-A-1" => None; "not configured")]
+    fn monitor_background(connector: &str) -> Option<String> {
+        let config: Config = toml::from_str(
+            r#"
+            [background]
+            path = "/default.jpg"
+
+            [[background.monitors]]
+            connector = "DP-1"
+            path = "/office.jpg"
+            "#,
+        )
+        .unwrap();
+        config.get_monitor_background(connector).map(str::to_string)
+    }
+
+    #[test]
+    fn clock_click_action_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            [widget.clock]
+            click_action = ["gnome-calendar", "--date", "today"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.widget.clock.click_action,
+            Some(vec![
+                "gnome-calendar".to_string(),
+                "--date".to_string(),
+                "today".to_string()
+            ])
+        );
+    }
+
+    #[test_case("" => 5; "default")]
+    #[test_case("error_timeout_secs = 0" => 0; "never cleared")]
+    fn error_timeout_secs(toml: &str) -> u64 {
+        let appearance: AppearanceSettings = toml::from_str(toml).unwrap();
+        appearance.error_timeout_secs
+    }
+
+    #[test]
+    fn widget_order_from_toml() {
+        let appearance: AppearanceSettings =
+            toml::from_str(r#"widget_order = "session-user""#).unwrap();
+        assert_eq!(appearance.widget_order, WidgetOrder::SessionUser);
+    }
 }
@@ -0,0 +1,363 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Semantic checks for the greeter config
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::read_to_string;
+use std::path::Path;
+
+use regex::Regex;
+use toml::Value;
+
+use crate::config::Config;
+use crate::sysutil::{find_executable, NormalUser};
+use crate::tomlutils::load_merged_toml;
+
+/// Severity of an entry in the config report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReportLevel {
+    Ok,
+    Warn,
+    Error,
+}
+
+impl Display for ReportLevel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Ok => write!(f, "OK"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// A single line in the config report
+pub struct ReportEntry {
+    level: ReportLevel,
+    /// The config option (or other item) that this entry is about
+    key: String,
+    /// Either the parsed value of the option, or a description of the problem with it
+    detail: String,
+}
+
+impl Display for ReportEntry {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.level == ReportLevel::Ok {
+            write!(f, "[{}] {} = {}", self.level, self.key, self.detail)
+        } else {
+            write!(f, "[{}] {}: {}", self.level, self.key, self.detail)
+        }
+    }
+}
+
+/// Report of all options parsed from the config file, along with any problems with them
+#[derive(Default)]
+pub struct ConfigReport {
+    entries: Vec<ReportEntry>,
+}
+
+impl ConfigReport {
+    /// Parse the config file at the given path and check it (and the custom CSS) for problems.
+    pub fn new(path: &Path, css_path: &Path) -> Self {
+        let mut report = Self::default();
+        report.check_css(css_path);
+
+        if !path.exists() {
+            report.push(
+                ReportLevel::Warn,
+                "config",
+                format!("'{}' not found; using defaults", path.display()),
+            );
+            return report;
+        }
+
+        let value: Value = match load_merged_toml(path) {
+            Ok(value) => value,
+            Err(err) => {
+                // Include the causes, such as the error in an included file and why it failed.
+                let detail = std::iter::successors(err.source(), |err| (*err).source())
+                    .fold(err.to_string(), |detail, source| {
+                        format!("{detail}: {source}")
+                    });
+                report.push(ReportLevel::Error, "config", detail);
+                return report;
+            }
+        };
+        let config: Config = match value.clone().try_into() {
+            Ok(config) => config,
+            Err(err) => {
+                report.push(ReportLevel::Error, "config", err.to_string());
+                return report;
+            }
+        };
+
+        let mut problems = Self::default();
+        problems.check_backgrounds(&config);
+        problems.check_commands(&config);
+        problems.check_pam_patterns(&config);
+
+        // List all parsed options, with their problems (if any) in place of them.
+        report.list_options(&value, "", &mut problems.entries);
+        report.entries.append(&mut problems.entries);
+        report.check_uid_limits();
+        report
+    }
+
+    fn push(&mut self, level: ReportLevel, key: &str, detail: String) {
+        self.entries.push(ReportEntry {
+            level,
+            key: key.to_string(),
+            detail,
+        });
+    }
+
+    /// Recursively list all options in the TOML value, replacing them with their problems if any.
+    fn list_options(&mut self, value: &Value, key: &str, problems: &mut Vec<ReportEntry>) {
+        match value {
+            Value::Table(table) => {
+                for (name, value) in table {
+                    let key = if key.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{key}.{name}")
+                    };
+                    self.list_options(value, &key, problems);
+                }
+            }
+            Value::Array(array) if array.iter().all(Value::is_table) && !array.is_empty() => {
+                for (i, value) in array.iter().enumerate() {
+                    self.list_options(value, &format!("{key}[{i}]"), problems);
+                }
+            }
+            _ => {
+                let (found, rest): (Vec<_>, Vec<_>) =
+                    problems.drain(..).partition(|entry| entry.key == key);
+                *problems = rest;
+                if found.is_empty() {
+                    self.push(ReportLevel::Ok, key, value.to_string());
+                } else {
+                    self.entries.extend(found);
+                }
+            }
+        }
+    }
+
+    /// Check that the custom CSS file (which is optional) can be read, if it exists.
+    fn check_css(&mut self, css_path: &Path) {
+        if !css_path.exists() {
+            return;
+        }
+        match read_to_string(css_path) {
+            Ok(_) => self.push(
+                ReportLevel::Ok,
+                "style",
+                format!("'{}'", css_path.display()),
+            ),
+            Err(err) => self.push(
+                ReportLevel::Error,
+                "style",
+                format!("'{}' can't be read: {err}", css_path.display()),
+            ),
+        }
+    }
+
+    /// Check that the UID limits in `login.defs` make sense.
+    fn check_uid_limits(&mut self) {
+        let NormalUser { uid_min, uid_max } = NormalUser::load();
+        if uid_min < uid_max {
+            self.push(
+                ReportLevel::Ok,
+                "login.defs",
+                format!("UID_MIN {uid_min}, UID_MAX {uid_max}"),
+            );
+        } else {
+            self.push(
+                ReportLevel::Error,
+                "login.defs",
+                format!("UID_MIN ({uid_min}) must be less than UID_MAX ({uid_max})"),
+            );
+        }
+    }
+
+    /// Check that the background images exist.
+    fn check_backgrounds(&mut self, config: &Config) {
+        // The single background image (if set) is shown first.
+        let has_path = config.get_background().is_some();
+        let backgrounds = config.get_backgrounds().enumerate().map(|(i, path)| {
+            let key = if i == 0 && has_path {
+                "background.path"
+            } else {
+                "background.paths"
+            };
+            (key, path)
+        });
+        let dark_background = config
+            .get_dark_background()
+            .map(|path| ("background.dark_path", path));
+
+        for (key, path) in backgrounds.chain(dark_background) {
+            if !Path::new(path).exists() {
+                self.push(ReportLevel::Warn, key, format!("'{path}' not found"));
+            }
+        }
+        for (i, (_, path)) in config.get_monitor_backgrounds().enumerate() {
+            if !Path::new(path).exists() {
+                self.push(
+                    ReportLevel::Warn,
+                    &format!("background.monitors[{i}].path"),
+                    format!("'{path}' not found"),
+                );
+            }
+        }
+    }
+
+    /// Check that the binaries of the system commands can be found.
+    fn check_commands(&mut self, config: &Config) {
+        let commands = config.get_sys_commands();
+        let mut checked = vec![
+            ("commands.reboot", &commands.reboot),
+            ("commands.poweroff", &commands.poweroff),
+            ("commands.x11_prefix", &commands.x11_prefix),
+        ];
+        if let Some(pre_session) = &commands.pre_session {
+            checked.push(("commands.pre_session", pre_session));
+        }
+        if let Some(osk) = &commands.osk {
+            checked.push(("commands.osk", osk));
+        }
+        if let Some(pre_login_cmd) = &config.get_guest().pre_login_cmd {
+            checked.push(("guest.pre_login_cmd", pre_login_cmd));
+        }
+        for (key, command) in checked {
+            match command.first() {
+                None => self.push(ReportLevel::Error, key, "command is empty".to_string()),
+                Some(binary) if find_executable(binary).is_none() => self.push(
+                    ReportLevel::Warn,
+                    key,
+                    format!("'{binary}' not found in PATH"),
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Check that the patterns for the PAM hint icons and error messages are valid regexes.
+    fn check_pam_patterns(&mut self, config: &Config) {
+        let hints = config
+            .get_appearance()
+            .pam_hints
+            .keys()
+            .map(|pattern| ("appearance.pam_hints", pattern));
+        let errors = config
+            .get_security()
+            .pam_error_map
+            .keys()
+            .map(|pattern| ("security.pam_error_map", pattern));
+        for (table, pattern) in hints.chain(errors) {
+            if let Err(err) = Regex::new(pattern) {
+                self.push(
+                    ReportLevel::Error,
+                    &format!("{table}.{pattern}"),
+                    format!("invalid pattern: {err}"),
+                );
+            }
+        }
+    }
+
+    /// Print the report to stdout.
+    pub fn print(&self) {
+        for entry in &self.entries {
+            println!("{entry}");
+        }
+    }
+
+    /// Get the exit code for this report.
+    ///
+    /// This is 0 if there are no problems, 1 if there are only warnings, and 2 if there are any
+    /// errors.
+    pub fn exit_code(&self) -> i32 {
+        match self.entries.iter().map(|entry| entry.level).max() {
+            None | Some(ReportLevel::Ok) => 0,
+            Some(ReportLevel::Warn) => 1,
+            Some(ReportLevel::Error) => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(&[] => 0; "empty")]
+    #[test_case(&[ReportLevel::Ok, ReportLevel::Ok] => 0; "only ok")]
+    #[test_case(&[ReportLevel::Ok, ReportLevel::Warn] => 1; "warnings")]
+    #[test_case(&[ReportLevel::Warn, ReportLevel::Error, ReportLevel::Ok] => 2; "errors")]
+    fn exit_code(levels: &[ReportLevel]) -> i32 {
+        let mut report = ConfigReport::default();
+        for level in levels {
+            report.push(*level, "key", String::new());
+        }
+        report.exit_code()
+    }
+
+    #[test]
+    fn css_readable() {
+        let path = std::env::temp_dir().join(format!("regreet-style-{}.css", std::process::id()));
+        std::fs::write(&path, "window { opacity: 1; }").unwrap();
+        let mut report = ConfigReport::default();
+        report.check_css(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.exit_code(), 0);
+        assert_eq!(report.entries.len(), 1);
+    }
+
+    #[test]
+    fn css_unreadable() {
+        // Directories can't be read as files.
+        let mut report = ConfigReport::default();
+        report.check_css(&std::env::temp_dir());
+        assert_eq!(report.exit_code(), 2);
+    }
+
+    #[test]
+    fn css_missing() {
+        let mut report = ConfigReport::default();
+        report.check_css(Path::new("/nonexistent/regreet.css"));
+        assert!(report.entries.is_empty());
+    }
+
+    #[test]
+    fn list_options() {
+        let value: Value = toml::from_str(
+            r#"
+            [appearance]
+            greeting_msg = "Welcome back!"
+
+            [background]
+            path = "/missing.jpg"
+            "#,
+        )
+        .unwrap();
+        let mut problems = vec![ReportEntry {
+            level: ReportLevel::Warn,
+            key: "background.path".to_string(),
+            detail: "'/missing.jpg' not found".to_string(),
+        }];
+
+        let mut report = ConfigReport::default();
+        report.list_options(&value, "", &mut problems);
+
+        let lines: Vec<_> = report.entries.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"[OK] appearance.greeting_msg = "Welcome back!""#,
+                "[WARN] background.path: '/missing.jpg' not found",
+            ]
+        );
+        assert!(problems.is_empty());
+    }
+}
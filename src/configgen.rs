@@ -0,0 +1,245 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Generation of an annotated config with all default options
+
+use toml::{Table, Value};
+
+use crate::config::Config;
+
+/// Comments explaining each section of the config, in the order in which the sections are output
+///
+/// Options that aren't set by default are listed commented out. Top-level options that aren't
+/// tables must come first, since they would otherwise end up in the preceding table.
+const SECTIONS: &[(&str, &str)] = &[
+    (
+        "user_defaults",
+        "\
+# Settings for specific users, given as one `[[user_defaults]]` table per user:
+#   username: System username of the user
+#   default_session: Name of the session to always select for this user
+#   greeting: Message shown instead of the greeting message when this user is selected
+#
+# For example:
+#   [[user_defaults]]
+#   username = \"teacher\"
+#   default_session = \"GNOME\"
+#   greeting = \"Good morning, {username}!\"",
+    ),
+    (
+        "appearance",
+        "\
+# Appearance of the login panel:
+#   greeting_msg: Message shown at the top of the login panel; \"{hostname}\" is replaced with the
+#     name of this machine, and \"{username}\" with the username of the selected user
+#   show_hostname: Whether to show the name of this machine in the top right corner
+#   show_session_tooltip: Whether to show the description of the selected session as a tooltip
+#   show_password_strength: Whether to show a strength indicator below visible inputs
+#   fade_in_ms: Duration (in milliseconds) of the fade-in animation on startup; 0 disables it
+#   widget_order: Order of the user and session rows; \"user-session\" or \"session-user\"
+#   message_align: Alignment of the greeting message; \"left\", \"center\" or \"right\"
+#   error_timeout_secs: Time (in seconds) after which an error disappears; 0 keeps it
+#   error_batch_delay_ms: Time (in milliseconds) to wait before showing an error
+#   usernames_min_width, sessions_min_width: Minimum widths (in pixels) of the combo boxes
+#   login_box.halign, login_box.valign: Position of the login panel on the screen; \"start\",
+#     \"center\" or \"end\"
+#
+# Options that aren't set by default:
+#   motd_path = \"/etc/motd\"  # File whose contents are shown below the greeting message
+#   panel_margin_top = 0  # Also panel_margin_bottom, panel_margin_start and panel_margin_end
+#   manual_user_tooltip = \"Manually enter username\"
+#   manual_session_tooltip = \"Manually enter session command\"
+#   login_tooltip = \"Log in\"
+#   auth_steps = 2  # Total number of authentication steps, shown as \"Step N of M\"
+#   auth_timeout_secs = 60  # Time without any input after which a login attempt is cancelled
+#   no_session_message = \"...\"  # Shown when no session or login shell is found for the user
+#
+# Icons shown next to info messages from PAM, keyed by (case-insensitive) regexes matching them:
+#   [appearance.pam_hints]
+#   \"fingerprint|swipe|finger\" = \"fingerprint-symbolic\"",
+    ),
+    (
+        "background",
+        "\
+# Background images:
+#   paths: Images shown one after the other (after `path`, if set); missing ones are skipped
+#   interval: How long each image is shown, if there are multiple (eg. \"30s\" or \"5m\")
+#   fit: How an image covers the screen; \"Fill\", \"Contain\", \"Cover\" or \"ScaleDown\"
+#   monitors: Images for specific monitors, as `[[background.monitors]]` tables with a
+#     `connector` (eg. \"DP-1\") and a `path`
+#
+# Options that aren't set by default:
+#   path = \"/usr/share/backgrounds/greeter.jpg\"
+#   dark_path = \"/usr/share/backgrounds/greeter-dark.jpg\"  # Used when the dark theme is preferred",
+    ),
+    (
+        "commands",
+        "\
+# System commands, each given as a list of the program and its arguments:
+#   reboot: Command used to reboot the system
+#   poweroff: Command used to shut down the system
+#   x11_prefix: Command prefix for X11 sessions to start the X server
+#   greetd_timeout_secs: Maximum time (in seconds) to wait for greetd to respond
+#   create_runtime_dir: Whether to create the user's runtime directory before starting a session
+#
+# Options that aren't set by default:
+#   pre_session = [\"/usr/local/bin/prepare-home\"]  # Run after authentication, before the session
+#   osk = [\"wvkbd-mobintl\"]  # On-screen keyboard shown when a password field is touched",
+    ),
+    (
+        "env",
+        "\
+# Environment variables passed to the session when it is started, such as:
+#   ENV_VARIABLE = \"value\"",
+    ),
+    (
+        "logging",
+        "\
+# Logging and error reporting:
+#   verbose_errors: Whether to show the type of errors from greetd along with their descriptions
+#
+# Options that aren't set by default:
+#   audit_path = \"/var/log/regreet/audit.log\"  # File where each login attempt is logged as JSON",
+    ),
+    (
+        "security",
+        "\
+# Protection against password guessing:
+#   lockout_secs: Time (in seconds) for which a user is locked out
+#   pam_error_map: Messages shown instead of errors from PAM, keyed by (case-insensitive) regexes
+#     matching the errors
+#
+# Options that aren't set by default:
+#   max_attempts = 5  # Consecutive failed login attempts after which a user is locked out",
+    ),
+    (
+        "auto_login",
+        "\
+# Automatically logging in the last user
+#
+# Options that aren't set by default:
+#   timeout_secs = 10  # Time without any key press or click after which the last user logs in",
+    ),
+    (
+        "users",
+        "\
+# Finding users:
+#   hidden: System usernames of users to hide, even if they are regular users
+#   show_search: Whether to show a search entry for filtering the users",
+    ),
+    (
+        "sessions",
+        "\
+# Finding sessions:
+#   blacklisted: Names of session files (without the \".desktop\" extension) to hide
+#   show_type_badge: Whether to show whether each session is for X11 (\"[X]\") or Wayland (\"[W]\")
+#   show_search: Whether to show a search entry for filtering the sessions
+#   group_by_category: Whether to group the sessions by the first of their categories (or by
+#     whether they're for X11 or Wayland, if they have none)
+#   category_order: Order of the groups of sessions; other groups are shown after these
+#   show_shells: Whether to show the login shells in /etc/shells below the other sessions",
+    ),
+    (
+        "guest",
+        "\
+# Logging in as a guest:
+#   enabled: Whether to show a \"Guest\" entry among the users
+#   username: System username that the guest logs in as
+#
+# Options that aren't set by default:
+#   session = \"Xfce\"  # Name of the session to select for the guest
+#   pre_login_cmd = [\"/usr/local/bin/make-guest-home\"]  # Run before logging in as the guest",
+    ),
+    (
+        "kiosk",
+        "\
+# Kiosk mode, which always logs in a single user into a single session:
+#   enabled: Whether to hide the choice of user and session (and the power buttons), and log in
+#     right away
+#   user: System username of the user to log in
+#   session: Name of the session to start",
+    ),
+    (
+        "widget",
+        "\
+# Widgets shown around the login panel:
+#   clock.format: strftime format of the clock's text
+#   clock.resolution: How often to update the clock's text (eg. \"500ms\")
+#   clock.label_width: Width (in pixels) that GTK is asked to make the clock
+#   clock.position: \"top-center\", \"top-left\", \"top-right\" or \"bottom-center\"
+#   battery.enabled: Whether to show the charge of the batteries (if any)
+#   battery.refresh_secs: Time (in seconds) between reading the battery status
+#   network.enabled: Whether to show whether the network is connected
+#
+# Options that aren't set by default:
+#   clock.timezone = \"America/Chicago\"  # IANA time zone used instead of the system one
+#   clock.click_action = [\"gnome-calendar\"]  # Command run when the clock is clicked",
+    ),
+];
+
+/// Comment explaining the GTK settings, which aren't set by default
+const GTK_COMMENT: &str = "\
+# GTK settings, which aren't set by default. For example:
+#   [GTK]
+#   application_prefer_dark_theme = true
+#   cursor_theme_name = \"Adwaita\"
+#   cursor_size = 24
+#   font_name = \"Cantarell 16\"
+#   text_scaling_factor = 1.0
+#   icon_theme_name = \"Adwaita\"
+#   icon_theme_names = [\"Papirus\", \"hicolor\"]  # Fallbacks if the above icon theme is missing
+#   theme_name = \"Adwaita\"";
+
+/// Generate a TOML config with all default options, annotated with comments explaining them.
+pub fn generate_config() -> String {
+    let Value::Table(mut config) =
+        Value::try_from(Config::default()).expect("Default config should be serializable")
+    else {
+        unreachable!("Config should be serialized as a table")
+    };
+
+    let mut sections = Vec::new();
+    for (key, comment) in SECTIONS {
+        let Some(value) = config.remove(*key) else {
+            continue;
+        };
+        let section = Table::from_iter([(key.to_string(), value)]);
+        let section =
+            toml::to_string_pretty(&section).expect("Default config should be serializable");
+        sections.push(format!("{comment}\n{section}"));
+    }
+    sections.push(GTK_COMMENT.to_string());
+
+    // Don't leave out any sections that don't have comments yet.
+    if !config.is_empty() {
+        sections
+            .push(toml::to_string_pretty(&config).expect("Default config should be serializable"));
+    }
+
+    sections.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_is_default() {
+        let generated: Value = toml::from_str(&generate_config()).unwrap();
+        assert_eq!(generated, Value::try_from(Config::default()).unwrap());
+    }
+
+    #[test]
+    fn all_sections_commented() {
+        let Value::Table(config) = Value::try_from(Config::default()).unwrap() else {
+            panic!("Config should be serialized as a table");
+        };
+        for key in config.keys() {
+            assert!(
+                SECTIONS.iter().any(|(section, _)| section == key),
+                "No comment for section: {key}"
+            );
+        }
+    }
+}
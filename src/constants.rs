@@ -39,6 +39,9 @@ const LOG_DIR: &str = env_or!("LOG_DIR", concatcp!("/var/log/", GREETER_NAME));
 /// Path to the log file
 pub const LOG_PATH: &str = concatcp!(LOG_DIR, "/log");
 
+/// The parent directory of the per-user runtime directories
+pub const RUNTIME_DIR_PARENT: &str = env_or!("RUNTIME_DIR_PARENT", "/run/user");
+
 /// Default command for rebooting
 pub const REBOOT_CMD: &str = env_or!("REBOOT_CMD", "reboot");
 /// Default command for shutting down
@@ -47,6 +50,10 @@ pub const POWEROFF_CMD: &str = env_or!("POWEROFF_CMD", "poweroff");
 /// Default greeting message
 pub const GREETING_MSG: &str = "Welcome back!";
 
+/// Default message when neither a session nor a login shell is found for the user
+pub const NO_SESSION_MSG: &str =
+    "No session or login shell found. Please enter a session command manually.";
+
 /// `:`-separated search path for `login.defs` file.
 ///
 /// By default this file is at `/etc/login.defs`, however some distros (e.g. Tumbleweed) move it to other locations.
@@ -92,5 +99,8 @@ pub const SESSION_DIRS: &str = env_or!(
     "/usr/share/xsessions:/usr/share/wayland-sessions"
 );
 
+/// Path to the file listing the valid login shells
+pub const SHELLS_PATH: &str = "/etc/shells";
+
 /// Command prefix for X11 sessions to start the X server
 pub const X11_CMD_PREFIX: &str = env_or!("X11_CMD_PREFIX", "startx /usr/bin/env");
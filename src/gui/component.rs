@@ -4,7 +4,11 @@
 
 //! Setup for using the greeter as a Relm4 component
 
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use relm4::{
     component::{AsyncComponent, AsyncComponentParts},
@@ -12,14 +16,32 @@ use relm4::{
     prelude::*,
     AsyncComponentSender,
 };
-use tracing::{debug, info, warn};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error, info, warn};
 
 #[cfg(feature = "gtk4_8")]
 use crate::config::BgFit;
+use crate::config::{MessageAlign, PanelAlign, WidgetOrder};
+use crate::sysutil::{session_dirs, SessionInfo, SessionType, SysUtil};
+use crate::watcher;
 
 use super::messages::{CommandMsg, InputMsg, UserSessInfo};
-use super::model::{Greeter, InputMode, Updates};
+use super::model::{
+    auto_login_text, lockout_text, motd_preview, password_strength, Greeter, InputMode, Updates,
+    HOSTNAME_PLACEHOLDER,
+};
 use super::templates::Ui;
+use super::widget::clock::ClockPosition;
+
+/// Icon shown for sessions without one
+const DEFAULT_SESSION_ICON: &str = "computer-symbolic";
+
+/// Time without further changes to the users to wait for before re-reading them
+///
+/// Tools like `useradd` write the user database in multiple steps, so this avoids reading it
+/// multiple times, or while it's incomplete.
+const USERS_DEBOUNCE: Duration = Duration::from_millis(200);
 
 /// Load GTK settings from the greeter config.
 fn setup_settings(model: &Greeter, root: &gtk::ApplicationWindow) {
@@ -41,14 +63,30 @@ fn setup_settings(model: &Greeter, root: &gtk::ApplicationWindow) {
         settings.set_gtk_cursor_theme_name(config.cursor_theme_name.as_deref());
     };
 
+    if let Some(cursor_size) = config.cursor_size {
+        debug!("Setting cursor size: {cursor_size}");
+        settings.set_gtk_cursor_theme_size(cursor_size.min(i32::MAX as u32) as i32);
+    };
+
     if let Some(font) = &config.font_name {
         debug!("Setting font: {font}");
         settings.set_gtk_font_name(config.font_name.as_deref());
     };
 
-    if let Some(icon_theme) = &config.icon_theme_name {
-        debug!("Setting icon theme: {icon_theme}");
-        settings.set_gtk_icon_theme_name(config.icon_theme_name.as_deref());
+    if let Some(xft_dpi) = config.xft_dpi() {
+        debug!("Setting font resolution: {xft_dpi}");
+        settings.set_gtk_xft_dpi(xft_dpi);
+    };
+
+    let icon_themes: Vec<_> = config.icon_theme_candidates().collect();
+    if !icon_themes.is_empty() {
+        let search_path = gtk::IconTheme::for_display(&WidgetExt::display(root)).search_path();
+        if let Some(icon_theme) = find_icon_theme(&icon_themes, &search_path) {
+            debug!("Setting icon theme: {icon_theme}");
+            settings.set_gtk_icon_theme_name(Some(icon_theme));
+        } else {
+            warn!("None of the icon themes {icon_themes:?} are installed; using the default");
+        }
     };
 
     if let Some(theme) = &config.theme_name {
@@ -57,24 +95,425 @@ fn setup_settings(model: &Greeter, root: &gtk::ApplicationWindow) {
     };
 }
 
+/// Find the first icon theme that is installed in any of the icon theme directories.
+fn find_icon_theme<'a>(names: &[&'a str], search_path: &[PathBuf]) -> Option<&'a str> {
+    names.iter().copied().find(|name| {
+        search_path
+            .iter()
+            .any(|dir| dir.join(name).join("index.theme").is_file())
+    })
+}
+
+/// Apply the configured margins between the login panel and the screen edges.
+fn setup_panel_margins(model: &Greeter, widgets: &GreeterWidgets) {
+    let appearance = model.config.get_appearance();
+    let frame = &widgets.ui.login_frame;
+
+    if let Some(margin) = appearance.panel_margin_top {
+        debug!("Setting login panel top margin: {margin}");
+        frame.set_margin_top(margin);
+    };
+    if let Some(margin) = appearance.panel_margin_bottom {
+        debug!("Setting login panel bottom margin: {margin}");
+        frame.set_margin_bottom(margin);
+    };
+    if let Some(margin) = appearance.panel_margin_start {
+        debug!("Setting login panel start margin: {margin}");
+        frame.set_margin_start(margin);
+    };
+    if let Some(margin) = appearance.panel_margin_end {
+        debug!("Setting login panel end margin: {margin}");
+        frame.set_margin_end(margin);
+    };
+}
+
+/// Get the widget alignment for the configured alignment of the login panel.
+fn panel_alignment(align: PanelAlign) -> gtk::Align {
+    match align {
+        PanelAlign::Start => gtk::Align::Start,
+        PanelAlign::Center => gtk::Align::Center,
+        PanelAlign::End => gtk::Align::End,
+    }
+}
+
+/// Move the login panel to its configured position on the screen.
+fn setup_panel_position(model: &Greeter, widgets: &GreeterWidgets) {
+    let position = model.config.get_appearance().login_box;
+    debug!("Setting login panel position: {position:?}");
+    let frame = &widgets.ui.login_frame;
+    frame.set_halign(panel_alignment(position.halign));
+    frame.set_valign(panel_alignment(position.valign));
+}
+
+/// Move the clock to its configured position on the screen.
+fn setup_clock_position(model: &Greeter, widgets: &GreeterWidgets) {
+    let position = model.config.widget.clock.position;
+    debug!("Setting clock position: {position:?}");
+
+    let clock_frame = &widgets.ui.clock_frame;
+    match position {
+        ClockPosition::TopCenter => {}
+        ClockPosition::TopLeft => clock_frame.set_halign(gtk::Align::Start),
+        ClockPosition::TopRight => {
+            // Share the top right corner with the other status widgets.
+            clock_frame.set_child(None::<&gtk::Widget>);
+            clock_frame.set_visible(false);
+            widgets.ui.panel_end_box.prepend(model.clock.widget());
+        }
+        ClockPosition::BottomCenter => {
+            // The bottom of the screen is already occupied by other widgets, so move the clock
+            // below them instead of overlaying it.
+            clock_frame.set_child(None::<&gtk::Widget>);
+            clock_frame.set_visible(false);
+            let bottom_clock_frame = &widgets.ui.bottom_clock_frame;
+            bottom_clock_frame.set_child(Some(model.clock.widget()));
+            bottom_clock_frame.set_visible(true);
+        }
+    }
+}
+
+/// Add the enabled status widgets to the end of the top panel.
+fn setup_panel_end(model: &Greeter, widgets: &GreeterWidgets) {
+    let appearance = model.config.get_appearance();
+    if appearance.show_hostname && !appearance.greeting_msg.contains(HOSTNAME_PLACEHOLDER) {
+        widgets
+            .ui
+            .panel_end_box
+            .append(&gtk::Label::new(Some(&model.hostname)));
+    }
+    if let Some(battery) = &model.battery {
+        widgets.ui.panel_end_box.append(battery.widget());
+    }
+    if let Some(network) = &model.network {
+        widgets.ui.panel_end_box.append(network.widget());
+    }
+    widgets
+        .ui
+        .panel_end
+        .set_visible(widgets.ui.panel_end_box.first_child().is_some());
+}
+
+/// Show the message of the day below the greeting message, if there is one.
+fn setup_motd(model: &Greeter, widgets: &GreeterWidgets) {
+    let Some(motd) = &model.motd else {
+        return;
+    };
+    let (preview, truncated) = motd_preview(motd);
+    widgets.ui.motd_label.set_label(&preview);
+    if truncated {
+        widgets.ui.motd_label.set_tooltip_text(Some(motd));
+    }
+    widgets.ui.motd_window.set_visible(true);
+}
+
+/// Arrange the user and session rows of the login panel in the configured order.
+fn setup_widget_order(model: &Greeter, widgets: &GreeterWidgets) {
+    let order = model.config.get_appearance().widget_order;
+    if order == WidgetOrder::default() {
+        return;
+    }
+    debug!("Setting login panel widget order: {order:?}");
+
+    let (user_row, sess_row) = order.rows();
+    let ui = &widgets.ui;
+    let rows: [(&gtk::Widget, i32); 13] = [
+        (ui.user_label.upcast_ref(), user_row),
+        (ui.usernames_box.upcast_ref(), user_row),
+        (ui.username_entry.upcast_ref(), user_row),
+        (ui.user_toggle.upcast_ref(), user_row),
+        (ui.avatar_frame.upcast_ref(), user_row),
+        (ui.session_label.upcast_ref(), sess_row),
+        (ui.sessions_box.upcast_ref(), sess_row),
+        (ui.session_entry.upcast_ref(), sess_row),
+        (ui.sess_toggle.upcast_ref(), sess_row),
+        // The input widgets replace the session widgets, so they must stay in the same row.
+        (ui.input_label.upcast_ref(), sess_row),
+        (ui.secret_entry.upcast_ref(), sess_row),
+        (ui.visible_entry.upcast_ref(), sess_row),
+        (ui.auth_spinner.upcast_ref(), sess_row),
+    ];
+
+    let grid = &ui.login_grid;
+    for (widget, row) in rows {
+        let (column, _, width, height) = grid.query_child(widget);
+        grid.remove(widget);
+        grid.attach(widget, column, row, width, height);
+    }
+}
+
+/// Column of the entry text in the model of a `ComboBoxText`
+const COMBO_BOX_TEXT_COLUMN: i32 = 0;
+
+/// Check whether the text matches the search query, ignoring case.
+///
+/// GLib's case folding is used, since it also handles characters like "ß" matching "SS".
+fn matches_search(text: &str, query: &str) -> bool {
+    gtk::glib::casefold(text).contains(gtk::glib::casefold(query).as_str())
+}
+
+/// Move the search entry above the combo box, and filter the combo box's entries with it.
+///
+/// The entries are matched by the text in the given columns of the combo box's model, after
+/// passing it through `search_text` (eg. to leave out badges). If `select_first` is set, then the
+/// first match is selected when the selected entry is filtered out. Otherwise, the selected entry
+/// is always shown, so that searching never changes it.
+fn setup_search(
+    grid: &gtk::Grid,
+    combo_box: &gtk::ComboBoxText,
+    search: &gtk::SearchEntry,
+    columns: Vec<i32>,
+    search_text: fn(&str) -> &str,
+    select_first: bool,
+) {
+    let Some(store) = combo_box.model() else {
+        return;
+    };
+
+    grid.insert_next_to(combo_box, gtk::PositionType::Top);
+    let (column, row, width, _) = grid.query_child(combo_box);
+    grid.remove(search);
+    grid.attach(search, column, row - 1, width, 1);
+
+    // The ID of the selected entry, if it's kept shown.
+    let selected = Rc::new(RefCell::new(None));
+    if !select_first {
+        selected.replace(combo_box.active_id());
+        let selected = selected.clone();
+        // Only track actual selections, and not the selection being cleared when repopulating.
+        combo_box.connect_changed(move |combo_box| {
+            if let Some(id) = combo_box.active_id() {
+                selected.replace(Some(id));
+            }
+        });
+    }
+
+    let id_column = combo_box.id_column();
+    let filter = gtk::TreeModelFilter::new(&store, None);
+    let query = search.downgrade();
+    filter.set_visible_func(move |store, iter| {
+        let Some(query) = query.upgrade() else {
+            return true;
+        };
+        let query = query.text();
+        // Also show any separators between entries, unless searching.
+        query.is_empty()
+            || selected.borrow().as_deref().is_some_and(|id| {
+                store.get::<Option<String>>(iter, id_column).as_deref() == Some(id)
+            })
+            || columns
+                .iter()
+                .filter_map(|&column| store.get::<Option<String>>(iter, column))
+                .any(|text| matches_search(search_text(&text), &query))
+    });
+    combo_box.set_model(Some(&filter));
+
+    let combo_box = combo_box.clone();
+    search.connect_search_changed(move |_| {
+        filter.refilter();
+        if select_first && combo_box.active().is_none() {
+            combo_box.set_active(Some(0));
+        }
+    });
+}
+
+/// Run the function on the combo box with its original model, if it's filtered.
+///
+/// Entries can only be added to and removed from a `ComboBoxText` through its original model.
+fn with_unfiltered(combo_box: &gtk::ComboBoxText, func: impl FnOnce()) {
+    let filter = combo_box.model().and_downcast::<gtk::TreeModelFilter>();
+    if let Some(filter) = &filter {
+        combo_box.set_model(Some(&filter.model()));
+    }
+    func();
+    if let Some(filter) = filter {
+        combo_box.set_model(Some(&filter));
+    }
+}
+
+/// Add the search entries for filtering the users and sessions, if enabled.
+fn setup_searches(model: &Greeter, widgets: &GreeterWidgets) {
+    let ui = &widgets.ui;
+    if model.config.get_users().show_search {
+        // Users are matched by both their full names (the entry text) and their usernames.
+        let id_column = ui.usernames_box.id_column();
+        // Selecting a different user changes their session, so leave that to the user by keeping
+        // the selected user shown.
+        setup_search(
+            &ui.login_grid,
+            &ui.usernames_box,
+            &ui.user_search,
+            vec![COMBO_BOX_TEXT_COLUMN, id_column],
+            |text| text,
+            false,
+        );
+    }
+    if model.config.get_sessions().show_search {
+        // Sessions are matched by both their displayed (possibly translated) names and their IDs,
+        // but not by any badges in their labels.
+        let id_column = ui.sessions_box.id_column();
+        setup_search(
+            &ui.login_grid,
+            &ui.sessions_box,
+            &ui.session_search,
+            vec![COMBO_BOX_TEXT_COLUMN, id_column],
+            without_type_badge,
+            true,
+        );
+    }
+}
+
+/// Override the default tooltips of the login panel's buttons, if configured.
+fn setup_tooltips(model: &Greeter, widgets: &GreeterWidgets) {
+    let appearance = model.config.get_appearance();
+    let ui = &widgets.ui;
+    let buttons: [(&gtk::Widget, &Option<String>); 3] = [
+        (ui.user_toggle.upcast_ref(), &appearance.manual_user_tooltip),
+        (
+            ui.sess_toggle.upcast_ref(),
+            &appearance.manual_session_tooltip,
+        ),
+        (ui.login_button.upcast_ref(), &appearance.login_tooltip),
+    ];
+    for (button, tooltip) in buttons {
+        if let Some(tooltip) = tooltip {
+            debug!("Setting tooltip: {tooltip}");
+            button.set_tooltip_text(Some(tooltip));
+        }
+    }
+}
+
+/// Get the width request for a configured minimum width, where 0 means no minimum.
+fn min_width_request(min_width: u32) -> Option<i32> {
+    (min_width > 0).then(|| min_width.min(i32::MAX as u32) as i32)
+}
+
+/// Apply the configured minimum widths of the users and sessions combo boxes.
+fn setup_min_widths(model: &Greeter, widgets: &GreeterWidgets) {
+    let appearance = model.config.get_appearance();
+    if let Some(width) = min_width_request(appearance.usernames_min_width) {
+        debug!("Setting minimum width of users: {width}");
+        widgets.ui.usernames_box.set_width_request(width);
+    };
+    if let Some(width) = min_width_request(appearance.sessions_min_width) {
+        debug!("Setting minimum width of sessions: {width}");
+        widgets.ui.sessions_box.set_width_request(width);
+    };
+}
+
+/// Get the widget alignment and text justification for the configured message alignment.
+fn message_alignment(align: MessageAlign) -> (gtk::Align, gtk::Justification) {
+    match align {
+        MessageAlign::Left => (gtk::Align::Start, gtk::Justification::Left),
+        MessageAlign::Center => (gtk::Align::Center, gtk::Justification::Center),
+        MessageAlign::Right => (gtk::Align::End, gtk::Justification::Right),
+    }
+}
+
+/// Get the CSS for fading in the greeter window over the given duration, if enabled.
+fn fade_in_css(fade_in_ms: u64) -> Option<String> {
+    if fade_in_ms == 0 {
+        return None;
+    }
+    Some(format!(
+        "window.fade-in {{ transition: opacity {fade_in_ms}ms ease; }}\n\
+         window.fade-in.faded-out {{ opacity: 0; }}"
+    ))
+}
+
+/// CSS for cropping user pictures into circles
+const AVATAR_CSS: &str = ".avatar { border-radius: 50%; }";
+
+/// Style the picture of the selected user.
+fn setup_avatar(root: &gtk::ApplicationWindow) {
+    let provider = gtk::CssProvider::new();
+    provider.load_from_data(AVATAR_CSS);
+    // Use the lowest priority, so that custom CSS can override this.
+    gtk::style_context_add_provider_for_display(
+        &WidgetExt::display(root),
+        &provider,
+        gtk::STYLE_PROVIDER_PRIORITY_FALLBACK,
+    );
+}
+
+/// Get the icon for the picture of a user, falling back to a generic icon if they have none.
+fn avatar_icon(avatar_path: Option<&PathBuf>) -> gtk::gio::Icon {
+    if let Some(path) = avatar_path {
+        gtk::gio::FileIcon::new(&gtk::gio::File::for_path(path)).upcast()
+    } else {
+        gtk::gio::ThemedIcon::new("avatar-default-symbolic").upcast()
+    }
+}
+
+/// Fade in the greeter window once it is shown, if configured.
+fn setup_fade_in(model: &Greeter, root: &gtk::ApplicationWindow) {
+    let css = if let Some(css) = fade_in_css(model.config.get_appearance().fade_in_ms) {
+        css
+    } else {
+        return;
+    };
+
+    debug!("Enabling fade-in animation");
+    let provider = gtk::CssProvider::new();
+    provider.load_from_data(&css);
+    gtk::style_context_add_provider_for_display(
+        &WidgetExt::display(root),
+        &provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    root.add_css_class("fade-in");
+    root.add_css_class("faded-out");
+
+    // Start the transition after the first frame, since the window needs to be drawn transparent
+    // at least once for the transition to be visible.
+    let reveal = |window: &gtk::ApplicationWindow| {
+        let window = window.clone();
+        gtk::glib::timeout_add_local_once(Duration::from_millis(16), move || {
+            window.remove_css_class("faded-out")
+        });
+    };
+    if root.is_mapped() {
+        reveal(root);
+    } else {
+        root.connect_map(move |window| reveal(window));
+    }
+}
+
+/// Show the background image that matches the dark theme preference, if a dark variant is set.
+///
+/// The background is updated whenever the dark theme preference changes.
+fn setup_background(model: &Greeter, widgets: &GreeterWidgets, root: &gtk::ApplicationWindow) {
+    let dark_path = if let Some(dark_path) = model.config.get_dark_background() {
+        dark_path.to_string()
+    } else {
+        return;
+    };
+    let light_path = model.current_background().map(str::to_string);
+
+    let background = widgets.ui.background.clone();
+    let set_background = move |settings: &gtk::Settings| {
+        let path = if settings.is_gtk_application_prefer_dark_theme() {
+            Some(&dark_path)
+        } else {
+            light_path.as_ref()
+        };
+        debug!("Setting background image: {path:?}");
+        background.set_filename(path);
+    };
+
+    let settings = root.settings();
+    set_background(&settings);
+    settings.connect_gtk_application_prefer_dark_theme_notify(set_background);
+}
+
 /// Populate the user and session combo boxes with entries.
 fn setup_users_sessions(model: &Greeter, widgets: &GreeterWidgets) {
     // The user that is shown during initial login
     let mut initial_username = None;
 
-    // Populate the usernames combo box.
-    for (user, username) in model.sys_util.get_users().iter() {
-        debug!("Found user: {user}");
-        if initial_username.is_none() {
-            initial_username = Some(username.clone());
-        }
-        widgets.ui.usernames_box.append(Some(username), user);
-    }
-
-    // Populate the sessions combo box.
-    for session in model.sys_util.get_sessions().keys() {
-        debug!("Found session: {session}");
-        widgets.ui.sessions_box.append(Some(session), session);
+    populate_users(model, widgets);
+    populate_sessions(model, widgets);
+    if let Some(username) = model.sys_util.get_users().values().next() {
+        initial_username = Some(username.clone());
     }
 
     // If the last user is known, show their login initially.
@@ -96,6 +535,426 @@ fn setup_users_sessions(model: &Greeter, widgets: &GreeterWidgets) {
     }
 }
 
+/// Populate the usernames combo box, keeping the selected user if they still exist.
+fn populate_users(model: &Greeter, widgets: &GreeterWidgets) {
+    let usernames_box = &widgets.ui.usernames_box;
+    let active_id = usernames_box.active_id();
+
+    with_unfiltered(usernames_box, || {
+        usernames_box.remove_all();
+        for (user, username) in model.sys_util.get_users().iter() {
+            // The guest's account is only shown as the guest entry.
+            if model.config.is_guest(username) {
+                continue;
+            }
+            debug!("Found user: {user}");
+            usernames_box.append(Some(username), user);
+        }
+
+        let guest = model.config.get_guest();
+        if guest.enabled {
+            usernames_box.append(Some(&guest.username), "Guest");
+        }
+    });
+
+    if active_id.is_some() && !usernames_box.set_active_id(active_id.as_deref()) {
+        info!("Previously selected user no longer exists; selecting the first user");
+        usernames_box.set_active(Some(0));
+    }
+}
+
+/// Populate the sessions combo box, keeping the selected session if it still exists.
+fn populate_sessions(model: &Greeter, widgets: &GreeterWidgets) {
+    let sessions_box = &widgets.ui.sessions_box;
+    let active_id = sessions_box.active_id();
+
+    let settings = model.config.get_sessions();
+    let sessions = model.sys_util.get_sessions();
+    // Login shells are always shown in their own group at the bottom.
+    let (mut shells, sessions): (Vec<_>, Vec<_>) = sessions
+        .iter()
+        .partition(|(_, info)| matches!(info.sess_type, SessionType::Shell));
+    let mut groups = if settings.group_by_category {
+        group_sessions(sessions, &settings.category_order)
+    } else {
+        vec![sessions]
+    };
+    shells.sort_by_key(|(session, _)| *session);
+    groups.push(shells);
+    groups.retain(|group| !group.is_empty());
+
+    let mut icons = HashMap::new();
+    with_unfiltered(sessions_box, || {
+        sessions_box.remove_all();
+        for (i, group) in groups.into_iter().enumerate() {
+            if i > 0 {
+                sessions_box.append(None, "");
+            }
+            for (session, info) in group {
+                debug!("Found session: {session}");
+                let label = session_label(
+                    info.localized_name.as_deref().unwrap_or(session),
+                    info.sess_type,
+                    settings.show_type_badge,
+                );
+                sessions_box.append(Some(session), &label);
+                icons.insert(session.clone(), session_icon(info.icon.as_deref()));
+            }
+        }
+    });
+
+    // Entries without an ID (ie. a session name) separate the groups of sessions.
+    let id_column = sessions_box.id_column();
+    sessions_box.set_row_separator_func(move |store, iter| {
+        store.get::<Option<String>>(iter, id_column).is_none()
+    });
+
+    // Look up the icon of each entry through its ID, which is the session name.
+    if let Some(renderer) = sessions_box
+        .cells()
+        .into_iter()
+        .find_map(|cell| cell.downcast::<gtk::CellRendererPixbuf>().ok())
+    {
+        sessions_box.set_cell_data_func(&renderer, move |_, cell, store, iter| {
+            let icon = store
+                .get::<Option<String>>(iter, id_column)
+                .and_then(|id| icons.get(&id).cloned());
+            cell.set_property("gicon", icon);
+        });
+    }
+
+    if active_id.is_some() && !sessions_box.set_active_id(active_id.as_deref()) {
+        info!("Previously selected session no longer exists; selecting the first session");
+        sessions_box.set_active(Some(0));
+    }
+}
+
+/// Show the icon of each session next to its name in the sessions combo box.
+///
+/// The icons themselves are set when populating the sessions.
+fn setup_session_icons(widgets: &GreeterWidgets) {
+    let sessions_box = &widgets.ui.sessions_box;
+    let renderer = gtk::CellRendererPixbuf::new();
+    sessions_box.pack_start(&renderer, false);
+    sessions_box.reorder(&renderer, 0);
+}
+
+/// Get the group of a session, which is its primary category, or its type if it has none.
+fn session_group(info: &SessionInfo) -> Option<&str> {
+    info.category.as_deref().or(match info.sess_type {
+        SessionType::X11 => Some("X11"),
+        SessionType::Wayland => Some("Wayland"),
+        SessionType::Shell | SessionType::Unknown => None,
+    })
+}
+
+/// Group the sessions by category, in the given order of categories.
+///
+/// Categories that aren't in the given order come after those that are (sorted by name), followed
+/// by sessions without any category. The sessions in each group are sorted by name.
+fn group_sessions<'a>(
+    sessions: impl IntoIterator<Item = (&'a String, &'a SessionInfo)>,
+    category_order: &[String],
+) -> Vec<Vec<(&'a String, &'a SessionInfo)>> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for (session, info) in sessions {
+        let group = session_group(info);
+        let rank = group
+            .and_then(|group| {
+                category_order
+                    .iter()
+                    .position(|category| category.eq_ignore_ascii_case(group))
+            })
+            .unwrap_or(category_order.len());
+        groups
+            .entry((rank, group.is_none(), group))
+            .or_default()
+            .push((session, info));
+    }
+
+    groups
+        .into_values()
+        .map(|mut group| {
+            group.sort_by_key(|(session, info)| info.localized_name.as_ref().unwrap_or(session));
+            group
+        })
+        .collect()
+}
+
+/// Get the text shown for a session, optionally with a badge for whether it's X11 or Wayland.
+fn session_label(name: &str, sess_type: SessionType, show_type_badge: bool) -> String {
+    let badge = match sess_type {
+        SessionType::X11 => "X",
+        SessionType::Wayland => "W",
+        SessionType::Shell | SessionType::Unknown => return name.to_string(),
+    };
+    if show_type_badge {
+        format!("{name} [{badge}]")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Get the name of a session from the text shown for it, leaving out any badge.
+fn without_type_badge(label: &str) -> &str {
+    label
+        .strip_suffix(" [X]")
+        .or_else(|| label.strip_suffix(" [W]"))
+        .unwrap_or(label)
+}
+
+/// Get the icon for a session, given its icon name or path (if any).
+fn session_icon(icon: Option<&str>) -> gtk::gio::Icon {
+    icon.and_then(|icon| {
+        gtk::gio::Icon::for_string(icon)
+            .map_err(|err| warn!("Invalid session icon '{icon}': {err}"))
+            .ok()
+    })
+    .unwrap_or_else(|| gtk::gio::ThemedIcon::new(DEFAULT_SESSION_ICON).upcast())
+}
+
+/// Check whether Caps Lock is on for the keyboard of the display that the widget is on.
+fn caps_lock_state(widget: &impl IsA<gtk::Widget>) -> bool {
+    widget
+        .display()
+        .default_seat()
+        .and_then(|seat| seat.keyboard())
+        .is_some_and(|keyboard| {
+            keyboard
+                .modifier_state()
+                .contains(gtk::gdk::ModifierType::LOCK_MASK)
+        })
+}
+
+/// Keep track of whether Caps Lock is on while the user types a secret.
+fn setup_caps_lock(sender: &AsyncComponentSender<Greeter>, widgets: &GreeterWidgets) {
+    let controller = gtk::EventControllerKey::new();
+    // The password entry's inner text widget handles the key presses, so they never bubble up to
+    // the entry itself.
+    controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let key_sender = sender.clone();
+    controller.connect_key_pressed(move |_, _, _, state| {
+        key_sender.input(InputMsg::CapsLock(
+            state.contains(gtk::gdk::ModifierType::LOCK_MASK),
+        ));
+        gtk::glib::Propagation::Proceed
+    });
+    // Pressing Caps Lock itself only changes the modifiers of the next key press.
+    let modifiers_sender = sender.clone();
+    controller.connect_modifiers(move |_, state| {
+        modifiers_sender.input(InputMsg::CapsLock(
+            state.contains(gtk::gdk::ModifierType::LOCK_MASK),
+        ));
+        gtk::glib::Propagation::Proceed
+    });
+    widgets.ui.secret_entry.add_controller(controller);
+}
+
+/// Lock the greeter to the kiosk user and session (if enabled), and start logging them in.
+fn setup_kiosk(model: &Greeter, sender: &AsyncComponentSender<Greeter>, widgets: &GreeterWidgets) {
+    let Some(kiosk) = model.config.get_kiosk() else {
+        return;
+    };
+    info!(
+        "Kiosk mode enabled for user '{}' and session '{}'",
+        kiosk.user, kiosk.session
+    );
+    if !model
+        .sys_util
+        .get_users()
+        .values()
+        .any(|username| *username == kiosk.user)
+    {
+        warn!("Kiosk user '{}' isn't among the found users", kiosk.user);
+    }
+    if !model.sys_util.get_sessions().contains_key(&kiosk.session) {
+        warn!(
+            "Kiosk session '{}' isn't among the found sessions",
+            kiosk.session
+        );
+    }
+
+    // Remove the widgets for choosing the user and session, so that they can't be shown again.
+    let ui = &widgets.ui;
+    let choosers: [&gtk::Widget; 11] = [
+        ui.user_label.upcast_ref(),
+        ui.usernames_box.upcast_ref(),
+        ui.username_entry.upcast_ref(),
+        ui.user_search.upcast_ref(),
+        ui.user_toggle.upcast_ref(),
+        ui.avatar_frame.upcast_ref(),
+        ui.session_label.upcast_ref(),
+        ui.sessions_box.upcast_ref(),
+        ui.session_entry.upcast_ref(),
+        ui.session_search.upcast_ref(),
+        ui.sess_toggle.upcast_ref(),
+    ];
+    for widget in choosers {
+        ui.login_grid.remove(widget);
+    }
+    ui.reboot_button.set_visible(false);
+    ui.poweroff_button.set_visible(false);
+
+    sender.input(InputMsg::Login {
+        input: String::new(),
+        info: UserSessInfo::extract(
+            &ui.usernames_box,
+            &ui.username_entry,
+            &ui.sessions_box,
+            &ui.session_entry,
+        ),
+    });
+}
+
+/// Start the on-screen keyboard (if configured) when an authentication input is touched.
+fn setup_osk(model: &Greeter, sender: &AsyncComponentSender<Greeter>, widgets: &GreeterWidgets) {
+    if model.config.get_sys_commands().osk.is_none() {
+        return;
+    }
+
+    let entries: [&gtk::Widget; 2] = [
+        widgets.ui.secret_entry.upcast_ref(),
+        widgets.ui.visible_entry.upcast_ref(),
+    ];
+    for entry in entries {
+        let touch = gtk::GestureClick::new();
+        touch.set_touch_only(true);
+        // The inner text widget of the entry would otherwise handle the touch by itself.
+        touch.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let touch_sender = sender.clone();
+        touch.connect_pressed(move |_, _, _, _| touch_sender.input(InputMsg::ShowKeyboard));
+        entry.add_controller(touch);
+
+        let focus = gtk::EventControllerFocus::new();
+        let focus_sender = sender.clone();
+        focus.connect_leave(move |_| focus_sender.input(InputMsg::HideKeyboard));
+        entry.add_controller(focus);
+    }
+}
+
+/// Cancel automatically logging in the last user when a key is pressed or the mouse is clicked.
+fn setup_auto_login_cancel(
+    model: &Greeter,
+    sender: &AsyncComponentSender<Greeter>,
+    root: &gtk::ApplicationWindow,
+) {
+    if model.updates.auto_login_secs.is_none() {
+        return;
+    }
+
+    let keys = gtk::EventControllerKey::new();
+    let key_sender = sender.clone();
+    keys.connect_key_pressed(move |_, _, _, _| {
+        key_sender.input(InputMsg::CancelAutoLogin);
+        gtk::glib::Propagation::Proceed
+    });
+    root.add_controller(keys);
+
+    let clicks = gtk::GestureClick::new();
+    // Handle clicks before the clicked widgets do, without stopping them from handling them.
+    clicks.set_propagation_phase(gtk::PropagationPhase::Capture);
+    let click_sender = sender.clone();
+    clicks.connect_pressed(move |_, _, _, _| click_sender.input(InputMsg::CancelAutoLogin));
+    root.add_controller(clicks);
+}
+
+/// Watch for changes to the users and sessions on the system, and refresh them when they change.
+fn setup_watcher(sender: &AsyncComponentSender<Greeter>) {
+    let mut changes = match watcher::watch(session_dirs()) {
+        Ok(changes) => changes,
+        Err(err) => {
+            warn!("Couldn't watch for changes to users and sessions: {err}");
+            return;
+        }
+    };
+
+    sender.command(|out, shutdown| {
+        shutdown
+            .register(async move {
+                loop {
+                    let msg = tokio::select! {
+                        Some(()) = changes.users.recv() => {
+                            // Wait until the users stop changing.
+                            while let Ok(Some(())) =
+                                timeout(USERS_DEBOUNCE, changes.users.recv()).await
+                            {}
+                            CommandMsg::RefreshUsers
+                        }
+                        Some(()) = changes.sessions.recv() => CommandMsg::RefreshSessions,
+                        else => break,
+                    };
+                    if out.send(msg).is_err() {
+                        break;
+                    }
+                }
+            })
+            .drop_on_shutdown()
+    });
+}
+
+/// Switch between the background images periodically, if there are multiple.
+fn setup_background_rotation(model: &Greeter, sender: &AsyncComponentSender<Greeter>) {
+    let count = model
+        .config
+        .get_backgrounds()
+        .filter(|path| Path::new(path).exists())
+        .count();
+    if count < 2 {
+        return;
+    }
+
+    let interval = model.config.get_background_interval();
+    sender.command(move |out, shutdown| {
+        shutdown
+            .register(async move {
+                loop {
+                    sleep(interval).await;
+                    if out.send(CommandMsg::NextBackground).is_err() {
+                        break;
+                    }
+                }
+            })
+            .drop_on_shutdown()
+    });
+}
+
+/// Notify the greeter when monitors are connected, so that it can move to them if needed.
+fn setup_monitor_hotplug(sender: &AsyncComponentSender<Greeter>, widgets: &GreeterWidgets) {
+    let display = widgets.ui.display();
+    let sender = sender.clone();
+    display
+        .monitors()
+        .connect_items_changed(move |_, _, _, added| {
+            if added > 0 {
+                let display_name = display.name();
+                sender.oneshot_command(async move { CommandMsg::MonitorAdded(display_name) })
+            }
+        });
+}
+
+/// Reload the config when the greeter receives `SIGHUP`.
+fn setup_reload(sender: &AsyncComponentSender<Greeter>) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            warn!("Couldn't listen for SIGHUP to reload the config: {err}");
+            return;
+        }
+    };
+
+    sender.command(|out, shutdown| {
+        shutdown
+            .register(async move {
+                while hangup.recv().await.is_some() {
+                    if out.send(CommandMsg::ReloadConfig).is_err() {
+                        break;
+                    }
+                }
+            })
+            .drop_on_shutdown()
+    });
+}
+
 /// The info required to initialize the greeter
 pub struct GreeterInit {
     pub config_path: PathBuf,
@@ -121,7 +980,7 @@ impl AsyncComponent for Greeter {
             #[template]
             Ui {
                 #[template_child]
-                background { set_filename: model.config.get_background() },
+                background { set_filename: model.current_background() },
 
                 #[template_child]
                 clock_frame {
@@ -134,6 +993,18 @@ impl AsyncComponent for Greeter {
                     set_label: &model.updates.message,
                 },
                 #[template_child]
+                password_change_label {
+                    #[track(model.updates.changed(Updates::password_change_mode()))]
+                    set_visible: model.updates.password_change_mode,
+                },
+                #[template_child]
+                message_icon {
+                    #[track(model.updates.changed(Updates::message_icon()))]
+                    set_visible: model.updates.message_icon.is_some(),
+                    #[track(model.updates.changed(Updates::message_icon()))]
+                    set_icon_name: model.updates.message_icon.as_deref(),
+                },
+                #[template_child]
                 session_label {
                     #[track(model.updates.changed(Updates::input_mode()))]
                     set_visible: !model.updates.is_input(),
@@ -152,11 +1023,30 @@ impl AsyncComponent for Greeter {
                         username_entry = ui.username_entry.clone(),
                         sessions_box = ui.sessions_box.clone(),
                         session_entry = ui.session_entry.clone(),
-                    ] => move |this| sender.input(
-                        Self::Input::UserChanged(
-                            UserSessInfo::extract(this, &username_entry, &sessions_box, &session_entry)
-                        )
-                    ),
+                    ] => move |this| {
+                        // Filtering the users can unselect the user, which isn't a choice made by
+                        // the user.
+                        if this.active().is_some() {
+                            sender.input(Self::Input::UserChanged(UserSessInfo::extract(
+                                this, &username_entry, &sessions_box, &session_entry
+                            )))
+                        }
+                    },
+                },
+                #[template_child]
+                user_search {
+                    #[track(
+                        model.updates.changed(Updates::manual_user_mode())
+                        || model.updates.changed(Updates::input_mode())
+                    )]
+                    set_visible: model.config.get_users().show_search
+                        && !model.updates.manual_user_mode
+                        && !model.updates.is_input(),
+                    #[track(
+                        model.updates.changed(Updates::manual_user_mode())
+                        && model.updates.manual_user_mode
+                    )]
+                    set_text: "",
                 },
                 #[template_child]
                 username_entry {
@@ -177,6 +1067,28 @@ impl AsyncComponent for Greeter {
                     set_visible: !model.updates.manual_sess_mode && !model.updates.is_input(),
                     #[track(model.updates.changed(Updates::active_session_id()))]
                     set_active_id: model.updates.active_session_id.as_deref(),
+                    // `ComboBoxText` doesn't support per-entry tooltips, so show the description
+                    // of the selected session instead.
+                    #[track(model.updates.changed(Updates::session_tooltip()))]
+                    set_tooltip_text: model.updates.session_tooltip.as_deref(),
+                    connect_changed[sender] => move |this| {
+                        sender.input(Self::Input::SessionChanged(this.active_id()))
+                    },
+                },
+                #[template_child]
+                session_search {
+                    #[track(
+                        model.updates.changed(Updates::manual_sess_mode())
+                        || model.updates.changed(Updates::input_mode())
+                    )]
+                    set_visible: model.config.get_sessions().show_search
+                        && !model.updates.manual_sess_mode
+                        && !model.updates.is_input(),
+                    #[track(
+                        model.updates.changed(Updates::manual_sess_mode())
+                        && model.updates.manual_sess_mode
+                    )]
+                    set_text: "",
                 },
                 #[template_child]
                 session_entry {
@@ -185,15 +1097,28 @@ impl AsyncComponent for Greeter {
                         || model.updates.changed(Updates::input_mode())
                     )]
                     set_visible: model.updates.manual_sess_mode && !model.updates.is_input(),
+                    #[track(
+                        model.updates.changed(Updates::manual_sess_mode())
+                        && model.updates.manual_sess_mode
+                    )]
+                    grab_focus: (),
                 },
                 #[template_child]
                 input_label {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: model.updates.is_input(),
+                    set_visible: model.updates.is_input()
+                        && model.updates.input_mode != InputMode::Info,
                     #[track(model.updates.changed(Updates::input_prompt()))]
                     set_label: &model.updates.input_prompt,
                 },
                 #[template_child]
+                auth_step_label {
+                    #[track(model.updates.changed(Updates::auth_step_text()))]
+                    set_visible: model.updates.auth_step_text.is_some(),
+                    #[track(model.updates.changed(Updates::auth_step_text()))]
+                    set_label: model.updates.auth_step_text.as_deref().unwrap_or_default(),
+                },
+                #[template_child]
                 secret_entry {
                     #[track(model.updates.changed(Updates::input_mode()))]
                     set_visible: model.updates.input_mode == InputMode::Secret,
@@ -204,6 +1129,7 @@ impl AsyncComponent for Greeter {
                     grab_focus: (),
                     #[track(model.updates.changed(Updates::input()))]
                     set_text: &model.updates.input,
+                    connect_changed => Self::Input::InputActivity,
                     connect_activate[
                         sender,
                         usernames_box = ui.usernames_box.clone(),
@@ -230,6 +1156,13 @@ impl AsyncComponent for Greeter {
                     grab_focus: (),
                     #[track(model.updates.changed(Updates::input()))]
                     set_text: &model.updates.input,
+                    connect_changed[
+                        sender,
+                        strength_bar = ui.password_strength.clone(),
+                    ] => move |this| {
+                        strength_bar.set_value(password_strength(&this.text()));
+                        sender.input(Self::Input::InputActivity);
+                    },
                     connect_activate[
                         sender,
                         usernames_box = ui.usernames_box.clone(),
@@ -246,15 +1179,68 @@ impl AsyncComponent for Greeter {
                     }
                 },
                 #[template_child]
+                auth_spinner {
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_visible: model.updates.input_mode == InputMode::Info,
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_spinning: model.updates.input_mode == InputMode::Info,
+                },
+                #[template_child]
+                password_strength {
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_visible: model.config.get_appearance().show_password_strength
+                        && model.updates.input_mode == InputMode::Visible,
+                },
+                #[template_child]
+                caps_lock_label {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::caps_lock_active())
+                    )]
+                    set_visible: model.updates.caps_lock_active
+                        && model.updates.input_mode == InputMode::Secret,
+                },
+                #[template_child]
+                auto_login_box {
+                    #[track(model.updates.changed(Updates::auto_login_secs()))]
+                    set_visible: model.updates.auto_login_secs.is_some(),
+                },
+                #[template_child]
+                auto_login_label {
+                    #[track(model.updates.changed(Updates::auto_login_secs()))]
+                    set_label: &model.updates.auto_login_secs.map_or_else(String::new, |secs| {
+                        auto_login_text(&model.auto_login_name, secs)
+                    }),
+                },
+                #[template_child]
+                auto_login_cancel { connect_clicked => Self::Input::CancelAutoLogin },
+                #[template_child]
+                lockout_label {
+                    #[track(model.updates.changed(Updates::locked_until()))]
+                    set_visible: model.updates.locked_until.is_some(),
+                    #[track(model.updates.changed(Updates::locked_until()))]
+                    set_label: &model.updates.locked_until.map_or_else(String::new, |until| {
+                        lockout_text(until.saturating_duration_since(Instant::now()))
+                    }),
+                },
+                #[template_child]
                 user_toggle {
                     #[track(model.updates.changed(Updates::input_mode()))]
                     set_sensitive: !model.updates.is_input(),
                     connect_clicked => Self::Input::ToggleManualUser,
                 },
                 #[template_child]
+                avatar {
+                    #[track(model.updates.changed(Updates::avatar_path()))]
+                    set_from_gicon: &avatar_icon(model.updates.avatar_path.as_ref()),
+                },
+                #[template_child]
                 sess_toggle {
                     #[track(model.updates.changed(Updates::input_mode()))]
                     set_visible: !model.updates.is_input(),
+                    // Manual session mode can also be enabled by the model.
+                    #[track(model.updates.changed(Updates::manual_sess_mode()))]
+                    set_active: model.updates.manual_sess_mode,
                     connect_clicked => Self::Input::ToggleManualSess,
                 },
                 #[template_child]
@@ -265,6 +1251,14 @@ impl AsyncComponent for Greeter {
                 },
                 #[template_child]
                 login_button {
+                    // There's nothing to submit while waiting on authentication, and locked out
+                    // users can't log in.
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::locked_until())
+                    )]
+                    set_sensitive: model.updates.input_mode != InputMode::Info
+                        && model.updates.locked_until.is_none(),
                     #[track(
                         model.updates.changed(Updates::input_mode())
                         && !model.updates.is_input()
@@ -298,6 +1292,9 @@ impl AsyncComponent for Greeter {
                 },
                 #[template_child]
                 error_info {
+                    // Errors that aren't cleared automatically need to be dismissed by the user.
+                    set_show_close_button: model.config.get_appearance().error_timeout_secs == 0,
+                    connect_response[sender] => move |_, _| sender.input(Self::Input::DismissError),
                     #[track(model.updates.changed(Updates::error()))]
                     set_revealed: model.updates.error.is_some(),
                 },
@@ -320,6 +1317,19 @@ impl AsyncComponent for Greeter {
                 widgets.window.fullscreen_on_monitor(monitor);
                 // For some reason, the GTK settings are reset when changing monitors, so re-apply them.
                 setup_settings(self, &widgets.window);
+
+                // The new monitor may have its own background, unless the dark one is preferred.
+                if self.config.get_dark_background().is_none()
+                    || !widgets
+                        .window
+                        .settings()
+                        .is_gtk_application_prefer_dark_theme()
+                {
+                    widgets
+                        .ui
+                        .background
+                        .set_filename(self.current_background());
+                }
             }
         }
     }
@@ -331,8 +1341,24 @@ impl AsyncComponent for Greeter {
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
         let mut model = Self::new(&input.config_path, input.demo).await;
+        // Start this before creating the widgets, so that they show the countdown from the start.
+        model.start_auto_login(&sender);
         let widgets = view_output!();
 
+        setup_panel_margins(&model, &widgets);
+        setup_panel_position(&model, &widgets);
+        setup_clock_position(&model, &widgets);
+        setup_panel_end(&model, &widgets);
+        setup_motd(&model, &widgets);
+        setup_widget_order(&model, &widgets);
+        setup_searches(&model, &widgets);
+        setup_tooltips(&model, &widgets);
+        setup_min_widths(&model, &widgets);
+
+        let (align, justify) = message_alignment(model.config.get_appearance().message_align);
+        widgets.ui.message_box.set_halign(align);
+        widgets.ui.message_label.set_justify(justify);
+
         // Make the info bar permanently visible, since it was made invisible during init. The
         // actual visuals are controlled by `InfoBar::set_revealed`.
         widgets.ui.error_info.set_visible(true);
@@ -367,7 +1393,19 @@ impl AsyncComponent for Greeter {
         // For some reason, the GTK settings are reset when changing monitors, so apply them after
         // full-screening.
         setup_settings(&model, &root);
+        setup_background(&model, &widgets, &root);
+        setup_background_rotation(&model, &sender);
+        setup_session_icons(&widgets);
         setup_users_sessions(&model, &widgets);
+        setup_kiosk(&model, &sender, &widgets);
+        setup_auto_login_cancel(&model, &sender, &root);
+        setup_watcher(&sender);
+        setup_monitor_hotplug(&sender, &widgets);
+        setup_reload(&sender);
+        setup_caps_lock(&sender, &widgets);
+        setup_osk(&model, &sender, &widgets);
+        setup_avatar(&root);
+        setup_fade_in(&model, &root);
 
         if input.css_path.exists() {
             debug!("Loading custom CSS from file: {}", input.css_path.display());
@@ -407,6 +1445,13 @@ impl AsyncComponent for Greeter {
                 self.sess_info = Some(info);
                 self.user_change_handler();
             }
+            Self::Input::SessionChanged(session) => self.session_change_handler(session.as_deref()),
+            Self::Input::InputActivity => self.last_input_activity = Instant::now(),
+            Self::Input::CapsLock(active) => self.updates.set_caps_lock_active(active),
+            Self::Input::CancelAutoLogin => self.cancel_auto_login(),
+            Self::Input::ShowKeyboard => self.show_keyboard(),
+            Self::Input::HideKeyboard => self.hide_keyboard(),
+            Self::Input::DismissError => self.updates.set_error(None),
             Self::Input::ToggleManualUser => self
                 .updates
                 .set_manual_user_mode(!self.updates.manual_user_mode),
@@ -419,11 +1464,15 @@ impl AsyncComponent for Greeter {
     }
 
     /// Perform the requested changes when a background task sends a message.
-    async fn update_cmd(
+    ///
+    /// This needs the widgets, since refreshing the users and sessions repopulates their combo
+    /// boxes.
+    async fn update_cmd_with_view(
         &mut self,
+        widgets: &mut Self::Widgets,
         msg: Self::CommandOutput,
         sender: AsyncComponentSender<Self>,
-        _root: &Self::Root,
+        root: &Self::Root,
     ) {
         debug!("Got command message: {msg:?}");
 
@@ -431,13 +1480,232 @@ impl AsyncComponent for Greeter {
         self.updates.reset();
 
         match msg {
-            Self::CommandOutput::ClearErr => self.updates.set_error(None),
-            Self::CommandOutput::HandleGreetdResponse(response) => {
-                self.handle_greetd_response(&sender, response).await
+            Self::CommandOutput::ShowErr(id, text) => self.show_error(&sender, id, text),
+            Self::CommandOutput::ClearErr(id) => self.clear_error(id),
+            Self::CommandOutput::LockoutTick => self.lockout_tick_handler(&sender),
+            Self::CommandOutput::AutoLoginTick => {
+                if self.auto_login_tick(&sender) {
+                    let ui = &widgets.ui;
+                    self.sess_info = Some(UserSessInfo::extract(
+                        &ui.usernames_box,
+                        &ui.username_entry,
+                        &ui.sessions_box,
+                        &ui.session_entry,
+                    ));
+                    // If the user needs a password, then greetd asks for it as usual.
+                    self.login_click_handler(&sender, String::new()).await;
+                }
+            }
+            Self::CommandOutput::AuthTimeout(id) => self.auth_timeout_handler(&sender, id).await,
+            Self::CommandOutput::HandleGreetdResponse(Ok(response)) => {
+                self.handle_greetd_response(&sender, response).await;
+                // Caps Lock may already be on before the user starts typing the secret.
+                if self.updates.changed(Updates::input_mode())
+                    && self.updates.input_mode == InputMode::Secret
+                {
+                    self.updates
+                        .set_caps_lock_active(caps_lock_state(&widgets.ui.secret_entry));
+                }
+            }
+            Self::CommandOutput::HandleGreetdResponse(Err(err)) => {
+                self.handle_greetd_error(&sender, err).await
+            }
+            Self::CommandOutput::NextBackground => {
+                self.background_index = self.background_index.wrapping_add(1);
+                // The dark background replaces all others while the dark theme is preferred.
+                let dark = self.config.get_dark_background().is_some()
+                    && root.settings().is_gtk_application_prefer_dark_theme();
+                if !dark {
+                    let path = self.current_background();
+                    debug!("Setting background image: {path:?}");
+                    widgets.ui.background.set_filename(path);
+                }
+            }
+            Self::CommandOutput::ReloadConfig => {
+                if self.reload_config() {
+                    // Only re-apply the settings that don't depend on the login panel's initial
+                    // state.
+                    setup_settings(self, root);
+                    setup_panel_margins(self, widgets);
+                    setup_panel_position(self, widgets);
+                    setup_tooltips(self, widgets);
+                    setup_min_widths(self, widgets);
+                }
+            }
+            Self::CommandOutput::RefreshUsers => {
+                info!("Users changed; refreshing them");
+                self.users_reload_id = self.users_reload_id.wrapping_add(1);
+                let id = self.users_reload_id;
+                let config = self.config.clone();
+                // Reading the user database may block, so do it in the background.
+                sender.spawn_oneshot_command(move || {
+                    CommandMsg::ReloadUsers(id, SysUtil::read_users(&config))
+                });
+            }
+            Self::CommandOutput::ReloadUsers(id, _) if id != self.users_reload_id => {
+                debug!("Ignoring outdated users from refresh {id}");
             }
+            Self::CommandOutput::ReloadUsers(_, users) => match users {
+                Ok(users) => {
+                    self.sys_util.set_users(users);
+                    populate_users(self, widgets);
+                }
+                Err(err) => error!("Couldn't refresh users: {err}"),
+            },
+            Self::CommandOutput::RefreshSessions => {
+                info!("Sessions changed; refreshing them");
+                self.sessions_reload_id = self.sessions_reload_id.wrapping_add(1);
+                let id = self.sessions_reload_id;
+                let session_dirs = self.sys_util.get_session_dirs().to_vec();
+                let config = self.config.clone();
+                // Reading the session files may block, so do it in the background.
+                sender.spawn_oneshot_command(move || {
+                    CommandMsg::ReloadSessions(id, SysUtil::init_sessions(&config, &session_dirs))
+                });
+            }
+            Self::CommandOutput::ReloadSessions(id, _) if id != self.sessions_reload_id => {
+                debug!("Ignoring outdated sessions from refresh {id}");
+            }
+            Self::CommandOutput::ReloadSessions(_, sessions) => match sessions {
+                Ok(sessions) => {
+                    self.sys_util.set_sessions(sessions);
+                    populate_sessions(self, widgets);
+                }
+                Err(err) => error!("Couldn't refresh sessions: {err}"),
+            },
             Self::CommandOutput::MonitorRemoved(display_name) => {
                 self.choose_monitor(display_name.as_str(), &sender)
             }
+            Self::CommandOutput::MonitorAdded(display_name) => {
+                // Avoid moving the greeter if the monitor that it's on is still connected.
+                if self
+                    .updates
+                    .monitor
+                    .as_ref()
+                    .is_some_and(|monitor| monitor.is_valid())
+                {
+                    debug!("Monitor added; staying on the current monitor");
+                } else {
+                    self.choose_monitor(display_name.as_str(), &sender)
+                }
+            }
+        };
+
+        self.update_view(widgets, sender);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(MessageAlign::Left => (gtk::Align::Start, gtk::Justification::Left); "left")]
+    #[test_case(MessageAlign::Center => (gtk::Align::Center, gtk::Justification::Center); "center")]
+    #[test_case(MessageAlign::Right => (gtk::Align::End, gtk::Justification::Right); "right")]
+    fn message_align(align: MessageAlign) -> (gtk::Align, gtk::Justification) {
+        message_alignment(align)
+    }
+
+    #[test_case(PanelAlign::Start => gtk::Align::Start; "start")]
+    #[test_case(PanelAlign::Center => gtk::Align::Center; "center")]
+    #[test_case(PanelAlign::End => gtk::Align::End; "end")]
+    fn panel_align(align: PanelAlign) -> gtk::Align {
+        panel_alignment(align)
+    }
+
+    #[test_case(0 => None; "no minimum")]
+    #[test_case(200 => Some(200); "minimum")]
+    #[test_case(u32::MAX => Some(i32::MAX); "too large")]
+    fn min_width(min_width: u32) -> Option<i32> {
+        min_width_request(min_width)
+    }
+
+    #[test_case(SessionType::Wayland, true => "Sway [W]"; "wayland")]
+    #[test_case(SessionType::X11, true => "Sway [X]"; "x11")]
+    #[test_case(SessionType::Shell, true => "Sway"; "shell")]
+    #[test_case(SessionType::Unknown, true => "Sway"; "unknown")]
+    #[test_case(SessionType::Wayland, false => "Sway"; "disabled")]
+    fn session_badge(sess_type: SessionType, show_type_badge: bool) -> String {
+        session_label("Sway", sess_type, show_type_badge)
+    }
+
+    #[test_case("Sway [W]" => "Sway"; "wayland")]
+    #[test_case("Xfce [X]" => "Xfce"; "x11")]
+    #[test_case("Bash" => "Bash"; "no badge")]
+    fn session_name(label: &str) -> &str {
+        without_type_badge(label)
+    }
+
+    #[test]
+    fn session_groups() {
+        let session = |sess_type, category: Option<&str>| SessionInfo {
+            command: Vec::new(),
+            sess_type,
+            localized_name: None,
+            category: category.map(String::from),
+            comment: None,
+            icon: None,
         };
+        let sessions: HashMap<String, SessionInfo> = [
+            ("Sway", session(SessionType::Wayland, Some("Tiling"))),
+            ("i3", session(SessionType::X11, Some("Tiling"))),
+            ("Xfce", session(SessionType::X11, None)),
+            ("GNOME", session(SessionType::Wayland, None)),
+            ("Kodi", session(SessionType::Wayland, Some("Media"))),
+            ("Shell", session(SessionType::Unknown, None)),
+            ("Cage", session(SessionType::Wayland, Some("Kiosk"))),
+        ]
+        .into_iter()
+        .map(|(name, info)| (name.to_string(), info))
+        .collect();
+        let order = ["Tiling".to_string(), "x11".to_string()];
+
+        let groups: Vec<Vec<_>> = group_sessions(&sessions, &order)
+            .into_iter()
+            .map(|group| group.into_iter().map(|(name, _)| name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                vec!["Sway", "i3"],
+                vec!["Xfce"],
+                vec!["Cage"],
+                vec!["Kodi"],
+                vec!["GNOME"],
+                vec!["Shell"],
+            ]
+        );
+    }
+
+    #[test]
+    fn icon_theme_fallback() {
+        let dir = std::env::temp_dir().join(format!("regreet-icons-{}", std::process::id()));
+        let missing = dir.join("missing");
+        let installed = dir.join("installed");
+        std::fs::create_dir_all(installed.join("Second")).unwrap();
+        std::fs::write(installed.join("Second").join("index.theme"), "").unwrap();
+        // A theme directory without an index isn't a usable theme.
+        std::fs::create_dir_all(installed.join("First")).unwrap();
+
+        let search_path = [missing, installed];
+        assert_eq!(
+            find_icon_theme(&["First", "Second", "Third"], &search_path),
+            Some("Second")
+        );
+        assert_eq!(find_icon_theme(&["First", "Third"], &search_path), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fade_in_disabled() {
+        assert_eq!(fade_in_css(0), None);
+    }
+
+    #[test]
+    fn fade_in_enabled() {
+        let css = fade_in_css(250).expect("No CSS for fade-in");
+        assert!(css.contains("transition: opacity 250ms ease;"));
+        assert!(css.contains("window.fade-in.faded-out { opacity: 0; }"));
     }
 }
@@ -4,10 +4,14 @@
 
 //! Message definitions for communication between the view and the model
 
+use std::io::Result as IOResult;
+
 use educe::Educe;
-use greetd_ipc::Response;
 use relm4::gtk::{glib::GString, prelude::*, ComboBoxText, Entry};
 
+use crate::client::GreetdResult;
+use crate::sysutil::{SessionMap, ShellMap, UserMap};
+
 #[derive(Debug)]
 /// Info about the current user and chosen session
 pub struct UserSessInfo {
@@ -52,6 +56,20 @@ pub enum InputMsg {
     Cancel,
     /// The current user was changed in the GUI.
     UserChanged(UserSessInfo),
+    /// The current session was changed in the GUI.
+    SessionChanged(Option<GString>),
+    /// The user typed in an authentication input.
+    InputActivity,
+    /// Caps Lock was turned on or off while typing a secret.
+    CapsLock(bool),
+    /// An authentication input was touched, so an on-screen keyboard may be needed.
+    ShowKeyboard,
+    /// An authentication input lost focus, so the on-screen keyboard isn't needed anymore.
+    HideKeyboard,
+    /// The error message was dismissed by the user.
+    DismissError,
+    /// Cancel automatically logging in the last user.
+    CancelAutoLogin,
     /// Toggle manual entry of user.
     ToggleManualUser,
     /// Toggle manual entry of session.
@@ -63,11 +81,39 @@ pub enum InputMsg {
 #[derive(Debug)]
 /// The messages sent to the sender to run tasks in the background
 pub enum CommandMsg {
-    /// Clear the error message.
-    ClearErr,
-    /// Handle a response received from greetd
-    HandleGreetdResponse(Response),
+    /// Show the error message with the given ID, after waiting for any errors following it.
+    ShowErr(u64, String),
+    /// Clear the error message with the given ID.
+    ClearErr(u64),
+    /// Update the countdown of a lockout after too many failed login attempts.
+    LockoutTick,
+    /// Count down to automatically logging in the last user.
+    AutoLoginTick,
+    /// The authentication input with the given ID wasn't submitted in time.
+    AuthTimeout(u64),
+    /// Handle a response (or a communication error) received from greetd
+    HandleGreetdResponse(GreetdResult),
+    /// Show the next background image.
+    NextBackground,
+    /// Read the config file again, since it may have changed.
+    ReloadConfig,
+    /// Re-read the users, since they changed on the system.
+    RefreshUsers,
+    /// Replace the users and their shells with the re-read ones, unless they've been re-read again
+    /// since.
+    ///
+    /// The u64 is the ID of the re-reading.
+    ReloadUsers(u64, IOResult<(UserMap, ShellMap)>),
+    /// Re-read the sessions, since they changed on the system.
+    RefreshSessions,
+    /// Replace the sessions with the re-read ones, unless they've been re-read again since.
+    ///
+    /// The u64 is the ID of the re-reading.
+    ReloadSessions(u64, IOResult<SessionMap>),
     /// Notify the greeter that a monitor was removed.
     // The Gstring is the name of the display.
     MonitorRemoved(GString),
+    /// Notify the greeter that a monitor was added.
+    // The Gstring is the name of the display.
+    MonitorAdded(GString),
 }
@@ -9,7 +9,9 @@ mod messages;
 mod model;
 mod templates;
 pub(crate) mod widget {
+    pub mod battery;
     pub mod clock;
+    pub mod network;
 }
 
 pub use component::GreeterInit;
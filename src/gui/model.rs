@@ -8,38 +8,72 @@
 
 //! The main logic for the greeter
 
-use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Formatter, Result as FmtResult};
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use greetd_ipc::{AuthMessageType, ErrorType, Response};
+use greetd_ipc::{codec::Error as GreetdError, AuthMessageType, ErrorType, Response};
+use regex::Regex;
 use relm4::{
     gtk::{
         gdk::{Display, Monitor},
+        glib,
         prelude::*,
     },
     AsyncComponentSender, Component, Controller,
 };
-use tokio::{sync::Mutex, time::sleep};
+use tokio::{process::Child, sync::Mutex, time::sleep};
 
+use crate::audit::{AuditLog, AuditRecord};
 use crate::cache::Cache;
 use crate::client::{AuthStatus, GreetdClient};
-use crate::config::Config;
-use crate::sysutil::{SessionInfo, SessionType, SysUtil};
+use crate::config::{Config, SecuritySettings};
+use crate::sysutil::{
+    passwd_from_name, prepare_runtime_dir, SessionInfo, SessionMap, SessionType, SysUtil,
+    RUNTIME_DIR_ENV_VAR,
+};
+use crate::tomlutils::load_merged_toml;
 
 use super::{
     messages::{CommandMsg, UserSessInfo},
-    widget::clock::Clock,
+    widget::{battery::Battery, clock::Clock, network::Network},
 };
 
-const ERROR_MSG_CLEAR_DELAY: u64 = 5;
-
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub(super) enum InputMode {
     None,
     Secret,
     Visible,
+    /// No input is needed, but authentication is in progress (eg. waiting for a hardware key)
+    Info,
+}
+
+impl InputMode {
+    /// Get the input mode needed for an auth message from greetd.
+    fn for_auth_message(auth_message_type: &AuthMessageType) -> Self {
+        match auth_message_type {
+            AuthMessageType::Secret => Self::Secret,
+            AuthMessageType::Visible => Self::Visible,
+            AuthMessageType::Info => Self::Info,
+            AuthMessageType::Error => Self::None,
+        }
+    }
+}
+
+impl std::fmt::Display for InputMode {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Secret => write!(f, "secret"),
+            Self::Visible => write!(f, "visible"),
+            Self::Info => write!(f, "info"),
+        }
+    }
 }
 
 // Fields only set by the model, that are meant to be read only by the widgets
@@ -47,6 +81,8 @@ pub(super) enum InputMode {
 pub(super) struct Updates {
     /// Message to be shown to the user
     pub(super) message: String,
+    /// Name of the icon shown next to the message, for hints about how to authenticate
+    pub(super) message_icon: Option<String>,
     /// Error message to be shown to the user below the prompt
     pub(super) error: Option<String>,
     /// Text in the password field
@@ -61,16 +97,297 @@ pub(super) struct Updates {
     pub(super) input_mode: InputMode,
     /// ID of the active session
     pub(super) active_session_id: Option<String>,
+    /// Description of the active session, shown as a tooltip
+    pub(super) session_tooltip: Option<String>,
     /// Time that is displayed
     pub(super) time: String,
     /// Monitor where the window is displayed
     pub(super) monitor: Option<Monitor>,
+    /// Text describing the current authentication step, if authenticating
+    pub(super) auth_step_text: Option<String>,
+    /// Path to the picture of the current user, if they have one
+    pub(super) avatar_path: Option<PathBuf>,
+    /// Time until which the current user is locked out after too many failed login attempts
+    pub(super) locked_until: Option<Instant>,
+    /// Whether Caps Lock is on while the user is entering a secret
+    pub(super) caps_lock_active: bool,
+    /// Seconds left until the last user is logged in automatically, if counting down
+    pub(super) auto_login_secs: Option<u64>,
+    /// Whether PAM is asking the user to change their (expired) password
+    pub(super) password_change_mode: bool,
 }
 
 impl Updates {
+    fn new(message: String) -> Self {
+        Self {
+            message,
+            message_icon: None,
+            error: None,
+            input: String::new(),
+            manual_user_mode: false,
+            manual_sess_mode: false,
+            input_mode: InputMode::None,
+            input_prompt: String::new(),
+            active_session_id: None,
+            session_tooltip: None,
+            tracker: 0,
+            time: "".to_string(),
+            monitor: None,
+            auth_step_text: None,
+            avatar_path: None,
+            locked_until: None,
+            caps_lock_active: false,
+            auto_login_secs: None,
+            password_change_mode: false,
+        }
+    }
+
     pub(super) fn is_input(&self) -> bool {
         self.input_mode != InputMode::None
     }
+
+    /// Clear the input and show the prompt from greetd for it.
+    ///
+    /// Prompts from PAM for changing an expired password are replaced with clearer ones, and the
+    /// user is told that they need to change their password.
+    fn prompt_for_input(&mut self, auth_message: &str) {
+        self.set_input(String::new());
+        if let Some(prompt) = password_change_prompt(auth_message) {
+            self.set_password_change_mode(true);
+            self.set_input_prompt(prompt.to_string());
+        } else {
+            self.set_input_prompt(auth_message.trim_end().to_string());
+        }
+    }
+}
+
+/// Get a clearer prompt for the given prompt from PAM, if it asks for changing the password.
+fn password_change_prompt(auth_message: &str) -> Option<&'static str> {
+    let auth_message = auth_message.to_lowercase();
+    if !auth_message.contains("password") {
+        None
+    } else if auth_message.contains("current") || auth_message.contains("old password") {
+        Some("Current password")
+    } else if auth_message.contains("retype") || auth_message.contains("repeat") {
+        Some("Confirm new password")
+    } else if auth_message.contains("new password") {
+        Some("New password")
+    } else {
+        None
+    }
+}
+
+/// Run the command (described by `kind`, eg. "pre-session") to prepare for logging in the user.
+///
+/// The username is passed in the `REGREET_USER` environment variable. If the command fails, its
+/// error output (or exit status, if there's no output) is returned.
+async fn run_user_command(kind: &str, command: &[String], username: &str) -> Result<(), String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| format!("{} command is empty", capitalize(kind)))?;
+    info!("Running {kind} command: {command:?}");
+
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .env("REGREET_USER", username)
+        .output()
+        .await
+        .map_err(|err| format!("Couldn't run {kind} command: {err}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        Err(format!(
+            "{} command failed with {}",
+            capitalize(kind),
+            output.status
+        ))
+    } else {
+        Err(stderr.to_string())
+    }
+}
+
+/// Get the path to the picture of a user with the given home directory, if they have one.
+///
+/// As per the freedesktop convention, this is `~/.face`, which may also be a symlink.
+fn face_path(home: &Path) -> Option<PathBuf> {
+    let path = home.join(".face");
+    path.is_file().then_some(path)
+}
+
+/// Read the message of the day from the given file, without any terminal color codes.
+///
+/// If the file is missing or empty, then there's no message.
+fn read_motd(path: &Path) -> Option<String> {
+    let motd = match read_to_string(path) {
+        Ok(motd) => motd,
+        Err(err) => {
+            if err.kind() != ErrorKind::NotFound {
+                warn!(
+                    "Couldn't read message of the day '{}': {err}",
+                    path.display()
+                );
+            }
+            return None;
+        }
+    };
+    let ansi_regex = Regex::new(r"\x1b\[[^m]*m").expect("Invalid regex for ANSI escape codes");
+    let motd = ansi_regex.replace_all(&motd, "");
+    let motd = motd.trim();
+    (!motd.is_empty()).then(|| motd.to_string())
+}
+
+/// Shorten the message of the day if it's too long, returning whether it was shortened.
+pub(super) fn motd_preview(motd: &str) -> (String, bool) {
+    match motd.char_indices().nth(MOTD_MAX_CHARS) {
+        Some((end, _)) => (format!("{}…", &motd[..end]), true),
+        None => (motd.to_string(), false),
+    }
+}
+
+/// Get the session to select for the given user.
+///
+/// The session configured for the user takes priority over the one they last used.
+fn initial_session(config: &Config, cache: &mut Cache, username: &str) -> Option<String> {
+    config
+        .get_default_session(username)
+        .or_else(|| cache.get_last_session(username))
+        .map(str::to_string)
+}
+
+/// Get the tooltip for the given session, which is its description (if it has one).
+fn session_tooltip(sessions: &SessionMap, session: Option<&str>) -> Option<String> {
+    session
+        .and_then(|session| sessions.get(session))
+        .and_then(|info| info.comment.clone())
+}
+
+/// Tracker of failed login attempts, to lock out users after too many of them
+#[derive(Default)]
+pub(super) struct Lockout {
+    /// Number of consecutive failed login attempts for the current user
+    failures: u32,
+    /// Maps each locked out user to the time until which they're locked out
+    locked: HashMap<String, Instant>,
+}
+
+impl Lockout {
+    /// Record a failed login attempt by the given user.
+    ///
+    /// This returns the time until which the user is locked out, if this attempt locked them out.
+    fn record_failure(
+        &mut self,
+        username: &str,
+        settings: &SecuritySettings,
+        now: Instant,
+    ) -> Option<Instant> {
+        let max_attempts = settings.max_attempts?;
+        self.failures += 1;
+        if self.failures < max_attempts {
+            return None;
+        }
+
+        self.failures = 0;
+        let until = now + Duration::from_secs(settings.lockout_secs);
+        self.locked.retain(|_, until| *until > now);
+        self.locked.insert(username.to_string(), until);
+        Some(until)
+    }
+
+    /// Forget the failed login attempts, such as when the user changes.
+    ///
+    /// This doesn't end an ongoing lockout, so that it can't be bypassed by switching users.
+    fn reset_failures(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Get the time until which the given user is locked out, if they are.
+    fn locked_until(&self, username: &str, now: Instant) -> Option<Instant> {
+        self.locked
+            .get(username)
+            .filter(|until| **until > now)
+            .copied()
+    }
+
+    /// Check whether any user is still locked out.
+    fn is_active(&self, now: Instant) -> bool {
+        self.locked.values().any(|until| *until > now)
+    }
+}
+
+/// Maximum number of characters of the message of the day to show, with the rest in a tooltip
+const MOTD_MAX_CHARS: usize = 500;
+
+/// Maximum number of consecutive info messages from greetd that are shown together
+const MAX_INFO_MESSAGES: usize = 5;
+
+/// The latest consecutive info messages from greetd (eg. "Fingerprint accepted")
+#[derive(Default)]
+pub(super) struct InfoMessages(VecDeque<String>);
+
+impl InfoMessages {
+    /// Add an info message, returning all kept messages as the text to show to the user.
+    fn push(&mut self, message: String) -> String {
+        if self.0.len() == MAX_INFO_MESSAGES {
+            self.0.pop_front();
+        }
+        self.0.push_back(message);
+        Vec::from_iter(self.0.iter().map(String::as_str)).join("\n")
+    }
+
+    /// Forget all info messages, so that the next one is shown on its own.
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Placeholder in the greeting message that is replaced with the hostname
+pub(super) const HOSTNAME_PLACEHOLDER: &str = "{hostname}";
+
+/// Placeholder in the greeting message that is replaced with the username of the selected user
+const USERNAME_PLACEHOLDER: &str = "{username}";
+
+/// Get the greeting message, with the hostname and the username (if a user is selected) filled in.
+fn greeting_text(greeting_msg: &str, hostname: &str, username: Option<&str>) -> String {
+    let text = greeting_msg.replace(HOSTNAME_PLACEHOLDER, hostname);
+    match username {
+        Some(username) => text.replace(USERNAME_PLACEHOLDER, username),
+        None => text,
+    }
+}
+
+/// Get the text telling the user how long they're locked out for.
+pub(super) fn lockout_text(remaining: Duration) -> String {
+    // Round up, so that the countdown doesn't show zero while still locked out.
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    format!("Too many failed attempts. Try again in {secs}s.")
+}
+
+/// Get the text telling the user when they'll be logged in automatically.
+pub(super) fn auto_login_text(name: &str, secs: u64) -> String {
+    format!("Logging in as {name} in {secs}…")
+}
+
+/// Get the text describing the progress of a multi-step authentication.
+pub(super) fn auth_step_text(step: usize, max_steps: Option<usize>) -> String {
+    if let Some(max_steps) = max_steps {
+        format!("Step {step} of {max_steps}")
+    } else {
+        format!("Step {step}")
+    }
+}
+
+/// Get the authentication step after greetd sends an auth message of the given type.
+///
+/// Only prompts for input count as steps; info and error messages don't.
+fn next_auth_step(step: usize, auth_message_type: &AuthMessageType) -> usize {
+    match auth_message_type {
+        AuthMessageType::Secret | AuthMessageType::Visible => step + 1,
+        AuthMessageType::Info | AuthMessageType::Error => step,
+    }
 }
 
 /// Capitalize the first letter of the string.
@@ -78,6 +395,44 @@ fn capitalize(string: &str) -> String {
     string[0..1].to_uppercase() + &string[1..]
 }
 
+/// Format an error from greetd for display, optionally prefixed with its type.
+fn format_greetd_error(error_type: &ErrorType, description: &str, verbose: bool) -> String {
+    if !verbose {
+        return description.to_string();
+    }
+    let error_type = match error_type {
+        ErrorType::AuthError => "AuthError",
+        ErrorType::Error => "Error",
+    };
+    format!("{error_type}: {description}")
+}
+
+/// Estimate the strength of a password on a scale from 0 (weakest) to 4 (strongest).
+///
+/// This is a simple heuristic based on the length and the kinds of characters used.
+pub(super) fn password_strength(password: &str) -> f64 {
+    let length = password.chars().count();
+    let has_lower = password.chars().any(char::is_lowercase);
+    let has_upper = password.chars().any(char::is_uppercase);
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    let has_other = password.chars().any(|c| !c.is_alphanumeric());
+    let kinds = [has_lower, has_upper, has_digit, has_other]
+        .into_iter()
+        .filter(|has_kind| *has_kind)
+        .count();
+
+    let score = match length {
+        0..=5 => return 0.0,
+        6..=7 => 1,
+        8..=11 => 2,
+        _ => 3,
+    } + usize::from(kinds >= 3);
+
+    // Passwords made of a single kind of character are easy to guess, regardless of length.
+    let score = if kinds == 1 { score.min(1) } else { score };
+    score as f64
+}
+
 /// Greeter model that holds its state
 pub struct Greeter {
     /// Client to communicate with greetd
@@ -86,54 +441,245 @@ pub struct Greeter {
     pub(super) sys_util: SysUtil,
     /// The cache that persists between logins
     pub(super) cache: Cache,
-    /// The config for this greeter
-    pub(super) config: Config,
+    /// The config for this greeter, which is shared with background tasks
+    pub(super) config: Arc<Config>,
+    /// Path to the config file, for reloading it
+    pub(super) config_path: PathBuf,
     /// Session info set after pressing login
     pub(super) sess_info: Option<UserSessInfo>,
     /// The updates from the model that are read by the view
     pub(super) updates: Updates,
     /// Is it run as demo
     pub(super) demo: bool,
+    /// Identifier of the latest error, so that timers for older errors don't affect it
+    pub(super) error_id: u64,
+    /// Log of login attempts, if enabled
+    pub(super) audit_log: Option<AuditLog>,
+    /// Failed login attempts, for locking out users after too many of them
+    pub(super) lockout: Lockout,
+    /// Identifier of the latest authentication input, so that timeouts for older ones are ignored
+    pub(super) auth_timeout_id: u64,
+    /// Identifier of the latest re-reading of the users, so that results of older ones are ignored
+    pub(super) users_reload_id: u64,
+    /// Identifier of the latest re-reading of the sessions, so that results of older ones are
+    /// ignored
+    pub(super) sessions_reload_id: u64,
+    /// When the user last typed in an authentication input
+    pub(super) last_input_activity: Instant,
+    /// Name of this machine, which can't change while the greeter is running
+    pub(super) hostname: String,
+    /// Message of the day shown below the greeting message, if any
+    pub(super) motd: Option<String>,
+    /// Process of the on-screen keyboard, if it's running
+    pub(super) osk_process: Option<Child>,
+    /// Index of the background image being shown, among the ones that exist
+    pub(super) background_index: usize,
+    /// Name of the user being logged in automatically, as shown to the user
+    pub(super) auto_login_name: String,
+    /// Info messages from greetd since it last asked for input
+    pub(super) info_messages: InfoMessages,
+    /// Number of the current authentication step, with 0 meaning no authentication in progress
+    pub(super) auth_step: usize,
+    /// Handlers for the monitors being removed, so that they can be replaced when choosing again
+    pub(super) monitor_handlers: Vec<(Monitor, glib::SignalHandlerId)>,
 
     pub(super) clock: Controller<Clock>,
+    /// Battery status widget, if enabled
+    pub(super) battery: Option<Controller<Battery>>,
+    /// Network connectivity widget, if enabled
+    pub(super) network: Option<Controller<Network>>,
 }
 
 impl Greeter {
     pub(super) async fn new(config_path: &Path, demo: bool) -> Self {
         let config = Config::new(config_path);
 
-        let updates = Updates {
-            message: config.get_default_message(),
-            error: None,
-            input: String::new(),
-            manual_user_mode: false,
-            manual_sess_mode: false,
-            input_mode: InputMode::None,
-            input_prompt: String::new(),
-            active_session_id: None,
-            tracker: 0,
-            time: "".to_string(),
-            monitor: None,
-        };
+        let hostname = glib::host_name().to_string();
+        let motd = config
+            .get_appearance()
+            .motd_path
+            .as_deref()
+            .and_then(read_motd);
+        let updates = Updates::new(greeting_text(
+            &config.get_default_message(),
+            &hostname,
+            None,
+        ));
+        let greetd_timeout =
+            Duration::from_secs(config.get_sys_commands().greetd_timeout_secs.get());
         let greetd_client = Arc::new(Mutex::new(
-            GreetdClient::new(demo)
+            GreetdClient::new(demo, greetd_timeout)
                 .await
                 .expect("Couldn't initialize greetd client"),
         ));
 
+        let audit_log = if let Some(path) = &config.get_logging().audit_path {
+            match AuditLog::open(path).await {
+                Ok(audit_log) => Some(audit_log),
+                Err(err) => {
+                    error!("Couldn't open audit log '{}': {err}", path.display());
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         let clock = Clock::builder()
             .launch(config.widget.clock.clone())
             .detach();
+        let battery = config.widget.battery.enabled.then(|| {
+            Battery::builder()
+                .launch(config.widget.battery.clone())
+                .detach()
+        });
+        let network = config.widget.network.enabled.then(|| {
+            Network::builder()
+                .launch(config.widget.network.clone())
+                .detach()
+        });
 
         Self {
             greetd_client,
             sys_util: SysUtil::new(&config).expect("Couldn't read available users and sessions"),
             cache: Cache::new(),
             sess_info: None,
-            config,
+            config: Arc::new(config),
+            config_path: config_path.to_path_buf(),
             updates,
             demo,
+            error_id: 0,
+            audit_log,
+            lockout: Lockout::default(),
+            auth_timeout_id: 0,
+            users_reload_id: 0,
+            sessions_reload_id: 0,
+            last_input_activity: Instant::now(),
+            hostname,
+            motd,
+            osk_process: None,
+            auto_login_name: String::new(),
+            info_messages: InfoMessages::default(),
+            auth_step: 0,
+            monitor_handlers: Vec::new(),
+            background_index: 0,
             clock,
+            battery,
+            network,
+        }
+    }
+
+    /// Set the current authentication step, with 0 meaning no authentication in progress.
+    fn set_auth_step(&mut self, step: usize) {
+        self.auth_step = step;
+        let text =
+            (step > 0).then(|| auth_step_text(step, self.config.get_appearance().auth_steps));
+        self.updates.set_auth_step_text(text);
+    }
+
+    /// Get the background image to show, skipping any that don't exist.
+    ///
+    /// The image configured for the current monitor takes priority over the others. If none of
+    /// them exist, then the theme's background is shown instead.
+    pub(super) fn current_background(&self) -> Option<&str> {
+        let monitor_background = self
+            .updates
+            .monitor
+            .as_ref()
+            .and_then(|monitor| monitor.connector())
+            .and_then(|connector| self.config.get_monitor_background(&connector))
+            .filter(|path| Path::new(path).exists());
+        if monitor_background.is_some() {
+            return monitor_background;
+        }
+
+        let backgrounds: Vec<_> = self
+            .config
+            .get_backgrounds()
+            .filter(|path| Path::new(path).exists())
+            .collect();
+        if backgrounds.is_empty() {
+            None
+        } else {
+            Some(backgrounds[self.background_index % backgrounds.len()])
+        }
+    }
+
+    /// Get the greeting message for the selected user (if any), with the placeholders filled in.
+    fn default_message(&self) -> String {
+        let username = self
+            .sess_info
+            .as_ref()
+            .filter(|_| !self.updates.manual_user_mode)
+            .and_then(|info| info.user_id.as_deref());
+        match username {
+            Some(username) => greeting_text(
+                &self.config.get_user_greeting(username),
+                &self.hostname,
+                Some(username),
+            ),
+            None => greeting_text(&self.config.get_default_message(), &self.hostname, None),
+        }
+    }
+
+    /// Read the config file again, keeping the current login attempt and selections.
+    ///
+    /// If the config file is invalid, then the current config is kept. This returns whether the
+    /// config was reloaded.
+    pub(super) fn reload_config(&mut self) -> bool {
+        info!("Reloading config from: {}", self.config_path.display());
+        match load_merged_toml(&self.config_path) {
+            Ok(config) => self.config = Arc::new(config),
+            Err(err) => {
+                error!(
+                    "Couldn't reload config '{}'; keeping the current one: {err}",
+                    self.config_path.display()
+                );
+                return false;
+            }
+        }
+        // The message shows the prompt from greetd during authentication, so keep that.
+        if !self.updates.is_input() {
+            self.updates.set_message(self.default_message());
+        }
+        true
+    }
+
+    /// Start the on-screen keyboard, if configured and not already running.
+    pub(super) fn show_keyboard(&mut self) {
+        if let Some(child) = &mut self.osk_process {
+            if matches!(child.try_wait(), Ok(None)) {
+                return;
+            }
+        }
+        let Some((program, args)) = self
+            .config
+            .get_sys_commands()
+            .osk
+            .as_ref()
+            .and_then(|command| command.split_first())
+        else {
+            return;
+        };
+
+        info!("Starting on-screen keyboard: {program}");
+        match tokio::process::Command::new(program)
+            .args(args)
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => self.osk_process = Some(child),
+            Err(err) => error!("Couldn't start on-screen keyboard '{program}': {err}"),
+        }
+    }
+
+    /// Stop the on-screen keyboard, if it's running.
+    pub(super) fn hide_keyboard(&mut self) {
+        if let Some(mut child) = self.osk_process.take() {
+            debug!("Stopping on-screen keyboard");
+            if let Err(err) = child.start_kill() {
+                warn!("Couldn't stop on-screen keyboard: {err}");
+            }
         }
     }
 
@@ -152,6 +698,11 @@ impl Greeter {
             }
         };
 
+        // The monitors are connected to again below, so drop the handlers from the last time.
+        for (monitor, handler) in self.monitor_handlers.drain(..) {
+            monitor.disconnect(handler);
+        }
+
         let mut chosen_monitor = None;
         for monitor in display
             .monitors()
@@ -163,10 +714,11 @@ impl Greeter {
             .filter(Monitor::is_valid)
         {
             let sender = sender.clone();
-            monitor.connect_invalidate(move |monitor| {
+            let handler = monitor.connect_invalidate(move |monitor| {
                 let display_name = monitor.display().name();
                 sender.oneshot_command(async move { CommandMsg::MonitorRemoved(display_name) })
             });
+            self.monitor_handlers.push((monitor.clone(), handler));
             if chosen_monitor.is_none() {
                 // Choose the first monitor.
                 chosen_monitor = Some(monitor);
@@ -231,7 +783,11 @@ impl Greeter {
         };
         self.updates.set_input(String::new());
         self.updates.set_input_mode(InputMode::None);
-        self.updates.set_message(self.config.get_default_message())
+        self.set_auth_step(0);
+        self.updates.set_password_change_mode(false);
+        self.updates.set_message(self.default_message());
+        self.updates.set_message_icon(None);
+        self.info_messages.clear();
     }
 
     /// Create a greetd session, i.e. start a login attempt for the current user.
@@ -259,20 +815,55 @@ impl Greeter {
             debug!("Manually entered session command is parsable");
         };
 
+        if let Some(until) = self.lockout.locked_until(&username, Instant::now()) {
+            let text = lockout_text(until.saturating_duration_since(Instant::now()));
+            self.display_error(
+                sender,
+                &text,
+                &format!("Login attempt by locked out user '{username}'"),
+            );
+            return;
+        }
+
+        if self.config.is_guest(&username) {
+            if let Some(command) = self.config.get_guest().pre_login_cmd.clone() {
+                if self.demo {
+                    info!("demo: skip guest pre-login command");
+                } else if let Err(err) = run_user_command("pre-login", &command, &username).await {
+                    self.display_error(sender, &err, &format!("Pre-login command failed: {err}"));
+                    return;
+                }
+            }
+        }
+
         info!("Creating session for user: {username}");
 
         // Create a session for the current user.
-        let response = self
+        let result = self
             .greetd_client
             .lock()
             .await
             .create_session(&username)
-            .await
-            .unwrap_or_else(|err| {
-                panic!("Failed to create session for username '{username}': {err}",)
-            });
+            .await;
+
+        match result {
+            Ok(response) => self.handle_greetd_response(sender, response).await,
+            Err(err) => self.handle_greetd_error(sender, err).await,
+        }
+    }
 
-        self.handle_greetd_response(sender, response).await;
+    /// Show an error in communicating with greetd, and reset the current login attempt.
+    pub(super) async fn handle_greetd_error(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        err: GreetdError,
+    ) {
+        self.cancel_click_handler().await;
+        self.display_error(
+            sender,
+            &capitalize(&err.to_string()),
+            &format!("Failed to communicate with greetd: {err}"),
+        );
     }
 
     /// This function handles a greetd response as follows:
@@ -280,7 +871,7 @@ impl Greeter {
     /// - if the response is an authentication message:
     ///     - for info and error messages (no input request), display/log the text and send an empty authentication response to greetd.
     ///       This allows for immediate greetd updates when using authentication procedures that don't use text input.
-    ///       Also reset input mode to `None`
+    ///       Also set the input mode to `Info` (showing a spinner until the next response) or `None` respectively
     ///     - for input requests (visible/secret), set the input mode accordingly and return
     /// - if the response is an error, display it and return
     ///
@@ -292,12 +883,21 @@ impl Greeter {
         sender: &AsyncComponentSender<Self>,
         response: Response,
     ) {
+        // Any pending info (and its spinner) is outdated by this response.
+        if self.updates.input_mode == InputMode::Info {
+            self.updates.set_input_mode(InputMode::None);
+        }
+
         match response {
             Response::Success => {
                 // Authentication was successful and the session may be started.
                 // This may happen on the first request, in which case logging in
                 // as the given user requires no authentication.
                 info!("Successfully logged in; starting session");
+                self.hide_keyboard();
+                self.set_auth_step(0);
+                self.updates.set_password_change_mode(false);
+                self.lockout.reset_failures();
                 self.start_session(sender).await;
                 return;
             }
@@ -305,38 +905,49 @@ impl Greeter {
                 auth_message,
                 auth_message_type,
             } => {
+                self.updates
+                    .set_input_mode(InputMode::for_auth_message(&auth_message_type));
+                self.set_auth_step(next_auth_step(self.auth_step, &auth_message_type));
+                if matches!(
+                    self.updates.input_mode,
+                    InputMode::Secret | InputMode::Visible
+                ) {
+                    self.start_auth_timeout(sender);
+                }
                 match auth_message_type {
                     AuthMessageType::Secret => {
                         // Greetd has requested input that should be hidden
                         // e.g.: a password
                         info!("greetd asks for a secret auth input: {auth_message}");
-                        self.updates.set_input_mode(InputMode::Secret);
-                        self.updates.set_input(String::new());
-                        self.updates
-                            .set_input_prompt(auth_message.trim_end().to_string());
+                        self.updates.prompt_for_input(&auth_message);
+                        self.info_messages.clear();
                         return;
                     }
                     AuthMessageType::Visible => {
                         // Greetd has requested input that need not be hidden
                         info!("greetd asks for a visible auth input: {auth_message}");
-                        self.updates.set_input_mode(InputMode::Visible);
-                        self.updates.set_input(String::new());
-                        self.updates
-                            .set_input_prompt(auth_message.trim_end().to_string());
+                        self.updates.prompt_for_input(&auth_message);
+                        self.info_messages.clear();
                         return;
                     }
                     AuthMessageType::Info => {
                         // Greetd has sent an info message that should be displayed
                         // e.g.: asking for a fingerprint
                         info!("greetd sent an info: {auth_message}");
-                        self.updates.set_input_mode(InputMode::None);
-                        self.updates.set_message(auth_message);
+                        self.updates.set_message_icon(
+                            self.config
+                                .get_pam_hint_icon(&auth_message)
+                                .map(str::to_string),
+                        );
+                        let message = self.info_messages.push(auth_message);
+                        self.updates.set_message(message);
                     }
                     AuthMessageType::Error => {
                         // Greetd has sent an error message that should be displayed and logged
-                        self.updates.set_input_mode(InputMode::None);
                         // Reset outdated info message, if any
-                        self.updates.set_message(self.config.get_default_message());
+                        self.updates.set_message(self.default_message());
+                        self.updates.set_message_icon(None);
+                        self.info_messages.clear();
                         self.display_error(
                             sender,
                             &capitalize(&auth_message),
@@ -350,15 +961,30 @@ impl Greeter {
                 error_type,
             } => {
                 // some general response error. This can be an authentication failure or a general error
+                let verbose = self.config.get_logging().verbose_errors;
+                // Errors from PAM can be cryptic, so show a friendlier message instead if there's one.
+                let shown = match self.config.get_pam_error_message(&description) {
+                    Some(message) => message.to_string(),
+                    None => capitalize(&description),
+                };
+                let display_text = format!(
+                    "Login failed: {}",
+                    format_greetd_error(&error_type, &shown, verbose)
+                );
+                self.audit(Some(&display_text)).await;
                 self.display_error(
                     sender,
-                    &format!("Login failed: {}", capitalize(&description)),
-                    &format!("Error from greetd: {description}"),
+                    &display_text,
+                    &format!(
+                        "Error from greetd: {}",
+                        format_greetd_error(&error_type, &description, true)
+                    ),
                 );
 
                 // In case this is an authentication error (e.g. wrong password), the session should be cancelled.
                 if let ErrorType::AuthError = error_type {
-                    self.cancel_click_handler().await
+                    self.cancel_click_handler().await;
+                    self.record_auth_failure(sender);
                 }
                 return;
             }
@@ -368,13 +994,8 @@ impl Greeter {
         let client = Arc::clone(&self.greetd_client);
         sender.oneshot_command(async move {
             debug!("Sending empty auth response to greetd");
-            let response = client
-                .lock()
-                .await
-                .send_auth_response(None)
-                .await
-                .unwrap_or_else(|err| panic!("Failed to respond to greetd: {err}"));
-            CommandMsg::HandleGreetdResponse(response)
+            let result = client.lock().await.send_auth_response(None).await;
+            CommandMsg::HandleGreetdResponse(result)
         });
     }
 
@@ -390,16 +1011,44 @@ impl Greeter {
             return;
         };
 
-        if let Some(last_session) = self.cache.get_last_session(&username) {
-            // Set the last session used by this user in the session combo box.
-            self.updates
-                .set_active_session_id(Some(last_session.to_string()));
+        self.updates.set_message(self.default_message());
+        self.updates.set_message_icon(None);
+        self.info_messages.clear();
+        self.lockout.reset_failures();
+        self.updates
+            .set_locked_until(self.lockout.locked_until(&username, Instant::now()));
+
+        let avatar_path = match passwd_from_name(&username) {
+            Ok(Some(passwd)) => face_path(Path::new(&passwd.dir)),
+            Ok(None) => None,
+            Err(err) => {
+                warn!("Couldn't look up user '{username}': {err}");
+                None
+            }
+        };
+        debug!("Picture for user '{username}': {avatar_path:?}");
+        self.updates.set_avatar_path(avatar_path);
+
+        if let Some(session) = initial_session(&self.config, &mut self.cache, &username) {
+            // Set the configured or last used session of this user in the session combo box.
+            self.updates.set_active_session_id(Some(session));
         } else {
             // Last session not found, so skip changing the session.
             info!("Last session for user '{username}' missing");
         };
     }
 
+    /// Event handler for selecting a different session in the `ComboBoxText`
+    ///
+    /// This shows the description of the session as a tooltip, if enabled.
+    pub(super) fn session_change_handler(&mut self, session: Option<&str>) {
+        if !self.config.get_appearance().show_session_tooltip {
+            return;
+        }
+        let tooltip = session_tooltip(self.sys_util.get_sessions(), session);
+        self.updates.set_session_tooltip(tooltip);
+    }
+
     /// Event handler for clicking the "Login" button
     ///
     /// This does one of the following, depending of the state of authentication:
@@ -413,6 +1062,10 @@ impl Greeter {
     ) {
         // Check if a password is needed. If not, then directly start the session.
         let auth_status = self.greetd_client.lock().await.get_auth_status().clone();
+        debug!(
+            "Login clicked with auth status: {auth_status}, input mode: {}",
+            self.updates.input_mode
+        );
         match auth_status {
             AuthStatus::Done => {
                 // No password is needed, but the session should've been already started by
@@ -435,19 +1088,24 @@ impl Greeter {
         self.updates.set_input(String::new());
 
         // Send the password, as authentication for the current user.
-        let resp = self
+        let result = self
             .greetd_client
             .lock()
             .await
             .send_auth_response(Some(input))
-            .await
-            .unwrap_or_else(|err| panic!("Failed to send input: {err}"));
+            .await;
 
-        self.handle_greetd_response(sender, resp).await;
+        match result {
+            Ok(response) => self.handle_greetd_response(sender, response).await,
+            Err(err) => self.handle_greetd_error(sender, err).await,
+        }
     }
 
     /// Get the currently selected username.
     fn get_current_username(&self) -> Option<String> {
+        if let Some(kiosk) = self.config.get_kiosk() {
+            return Some(kiosk.user.clone());
+        }
         let info = self.sess_info.as_ref().expect("No session info set yet");
         if self.updates.manual_user_mode {
             debug!(
@@ -470,6 +1128,17 @@ impl Greeter {
         &mut self,
         sender: &AsyncComponentSender<Self>,
     ) -> (Option<String>, Option<SessionInfo>) {
+        if let Some(kiosk) = self.config.get_kiosk() {
+            let session = kiosk.session.clone();
+            return match self.sys_util.get_sessions().get(session.as_str()) {
+                Some(sess_info) => (Some(session), Some(sess_info.clone())),
+                None => {
+                    let error_msg = format!("Kiosk session '{session}' not found");
+                    self.display_error(sender, &error_msg, &error_msg);
+                    (None, None)
+                }
+            };
+        }
         let info = self.sess_info.as_ref().expect("No session info set yet");
         if self.updates.manual_sess_mode {
             debug!(
@@ -482,6 +1151,10 @@ impl Greeter {
                     Some(SessionInfo {
                         command: cmd,
                         sess_type: SessionType::Unknown,
+                        localized_name: None,
+                        category: None,
+                        comment: None,
+                        icon: None,
                     }),
                 )
             } else {
@@ -518,12 +1191,17 @@ impl Greeter {
                     Some(SessionInfo {
                         command: cmd.clone(),
                         sess_type: SessionType::Unknown,
+                        localized_name: None,
+                        category: None,
+                        comment: None,
+                        icon: None,
                     }),
                 )
             } else {
-                // No login shell exists.
-                let error_msg = "No session or login shell found";
-                self.display_error(sender, error_msg, error_msg);
+                // No login shell exists, so let the user enter the session command manually.
+                let error_msg = self.config.get_no_session_message().to_string();
+                self.display_error(sender, &error_msg, &error_msg);
+                self.updates.set_manual_sess_mode(true);
                 (None, None)
             }
         }
@@ -549,13 +1227,57 @@ impl Greeter {
             SessionType::Wayland => {
                 environment.push("XDG_SESSION_TYPE=wayland".to_string());
             }
+            SessionType::Shell => {
+                environment.push("XDG_SESSION_TYPE=tty".to_string());
+            }
             SessionType::Unknown => {}
         };
+        let create_runtime_dir = self.config.get_sys_commands().create_runtime_dir && !self.demo;
         for (k, v) in env {
+            // The runtime directory must be specific to the user, so it's set below instead.
+            if create_runtime_dir && k == RUNTIME_DIR_ENV_VAR {
+                warn!(
+                    "Ignoring {RUNTIME_DIR_ENV_VAR} in the config, since it's shared by all users"
+                );
+                continue;
+            }
             environment.push(format!("{}={}", k, v));
         }
 
-        if let Some(username) = self.get_current_username() {
+        if create_runtime_dir {
+            if let Some(username) = self.get_current_username() {
+                match passwd_from_name(&username) {
+                    Ok(Some(passwd)) => match prepare_runtime_dir(passwd.uid, passwd.gid) {
+                        Ok(path) => {
+                            environment.push(format!("{RUNTIME_DIR_ENV_VAR}={}", path.display()))
+                        }
+                        // Some compositors create the runtime directory themselves.
+                        Err(err) => {
+                            warn!("Couldn't create runtime directory for user '{username}': {err}")
+                        }
+                    },
+                    Ok(None) => warn!("User '{username}' not found; skipping runtime directory"),
+                    Err(err) => warn!("Couldn't look up user '{username}': {err}"),
+                }
+            }
+        }
+
+        if let Some(command) = &self.config.get_sys_commands().pre_session {
+            let username = self.get_current_username().unwrap_or_default();
+            if self.demo {
+                info!("demo: skip pre-session command");
+            } else if let Err(err) = run_user_command("pre-session", command, &username).await {
+                self.display_error(sender, &err, &format!("Pre-session command failed: {err}"));
+                self.cancel_click_handler().await;
+                return;
+            }
+        }
+
+        // Guest logins are temporary, so they shouldn't be remembered.
+        if let Some(username) = self
+            .get_current_username()
+            .filter(|username| !self.config.is_guest(username))
+        {
             self.cache.set_last_user(&username);
             if let Some(session) = session {
                 self.cache.set_last_session(&username, &session);
@@ -571,33 +1293,234 @@ impl Greeter {
         }
 
         // Start the session.
-        let response = self
+        let result = self
             .greetd_client
             .lock()
             .await
             .start_session(info.command, environment)
-            .await
-            .unwrap_or_else(|err| panic!("Failed to start session: {err}"));
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                self.handle_greetd_error(sender, err).await;
+                return;
+            }
+        };
 
         match response {
             Response::Success => {
                 info!("Session successfully started");
+                self.audit(None).await;
                 std::process::exit(0);
             }
 
             Response::AuthMessage { .. } => unimplemented!(),
 
-            Response::Error { description, .. } => {
+            Response::Error {
+                description,
+                error_type,
+            } => {
                 self.cancel_click_handler().await;
+                let display_text = if self.config.get_logging().verbose_errors {
+                    format!(
+                        "Failed to start session: {}",
+                        format_greetd_error(&error_type, &description, true)
+                    )
+                } else {
+                    "Failed to start session".to_string()
+                };
+                self.audit(Some(&display_text)).await;
                 self.display_error(
                     sender,
-                    "Failed to start session",
-                    &format!("Failed to start session; error: {description}"),
+                    &display_text,
+                    &format!(
+                        "Failed to start session; error: {}",
+                        format_greetd_error(&error_type, &description, true)
+                    ),
                 );
             }
         }
     }
 
+    /// Count a failed login attempt by the current user, and lock them out if needed.
+    fn record_auth_failure(&mut self, sender: &AsyncComponentSender<Self>) {
+        let username = if let Some(username) = self.get_current_username() {
+            username
+        } else {
+            return;
+        };
+        let settings = self.config.get_security();
+        if let Some(until) = self
+            .lockout
+            .record_failure(&username, settings, Instant::now())
+        {
+            warn!(
+                "Locking out user '{username}' for {}s after too many failed login attempts",
+                settings.lockout_secs
+            );
+            self.updates.set_locked_until(Some(until));
+            Self::schedule_lockout_tick(sender);
+        }
+    }
+
+    fn schedule_lockout_tick(sender: &AsyncComponentSender<Self>) {
+        sender.oneshot_command(async {
+            sleep(Duration::from_secs(1)).await;
+            CommandMsg::LockoutTick
+        });
+    }
+
+    /// Update the lockout countdown, and keep ticking while any user is locked out.
+    pub(super) fn lockout_tick_handler(&mut self, sender: &AsyncComponentSender<Self>) {
+        let now = Instant::now();
+        let locked_until = self
+            .get_current_username()
+            .and_then(|username| self.lockout.locked_until(&username, now));
+        // Always mark this as changed, so that the countdown is redrawn.
+        self.updates
+            .update_locked_until(|until| *until = locked_until);
+        if self.lockout.is_active(now) {
+            Self::schedule_lockout_tick(sender);
+        }
+    }
+
+    /// Start counting down to logging in the last user, if an auto-login timeout is set.
+    pub(super) fn start_auto_login(&mut self, sender: &AsyncComponentSender<Self>) {
+        let Some(timeout) = self.config.get_auto_login().timeout_secs else {
+            return;
+        };
+        // The kiosk user is logged in right away instead.
+        if self.config.get_kiosk().is_some() {
+            return;
+        }
+        let Some(username) = self.cache.get_last_user() else {
+            return;
+        };
+        self.auto_login_name = self
+            .sys_util
+            .get_users()
+            .iter()
+            .find_map(|(name, user)| (user == username).then_some(name))
+            .map_or(username, String::as_str)
+            .to_string();
+
+        info!("Logging in '{username}' automatically in {timeout}s");
+        self.updates.set_auto_login_secs(Some(timeout));
+        Self::schedule_auto_login_tick(sender);
+    }
+
+    fn schedule_auto_login_tick(sender: &AsyncComponentSender<Self>) {
+        sender.oneshot_command(async {
+            sleep(Duration::from_secs(1)).await;
+            CommandMsg::AutoLoginTick
+        });
+    }
+
+    /// Count down to logging in the last user, returning whether it's time to log in.
+    pub(super) fn auto_login_tick(&mut self, sender: &AsyncComponentSender<Self>) -> bool {
+        match self.updates.auto_login_secs {
+            // The auto-login was cancelled.
+            None => false,
+            Some(secs) if secs <= 1 => {
+                self.updates.set_auto_login_secs(None);
+                true
+            }
+            Some(secs) => {
+                self.updates.set_auto_login_secs(Some(secs - 1));
+                Self::schedule_auto_login_tick(sender);
+                false
+            }
+        }
+    }
+
+    /// Stop counting down to logging in the last user.
+    pub(super) fn cancel_auto_login(&mut self) {
+        if self.updates.auto_login_secs.is_some() {
+            info!("Cancelled logging in automatically");
+            self.updates.set_auto_login_secs(None);
+        }
+    }
+
+    /// Start counting down to cancelling the login attempt, if an authentication timeout is set.
+    fn start_auth_timeout(&mut self, sender: &AsyncComponentSender<Self>) {
+        self.auth_timeout_id = self.auth_timeout_id.wrapping_add(1);
+        self.last_input_activity = Instant::now();
+        if let Some(timeout) = self.config.get_appearance().auth_timeout_secs {
+            Self::schedule_auth_timeout(sender, self.auth_timeout_id, Duration::from_secs(timeout));
+        }
+    }
+
+    fn schedule_auth_timeout(sender: &AsyncComponentSender<Self>, id: u64, delay: Duration) {
+        sender.oneshot_command(async move {
+            sleep(delay).await;
+            CommandMsg::AuthTimeout(id)
+        });
+    }
+
+    /// Event handler for the countdown of the authentication input with the given ID running out
+    ///
+    /// If the user typed something during the countdown, then this waits until the timeout has
+    /// passed since then.
+    pub(super) async fn auth_timeout_handler(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        id: u64,
+    ) {
+        let timeout = if let Some(timeout) = self.config.get_appearance().auth_timeout_secs {
+            Duration::from_secs(timeout)
+        } else {
+            return;
+        };
+        if id != self.auth_timeout_id
+            || !matches!(
+                self.updates.input_mode,
+                InputMode::Secret | InputMode::Visible
+            )
+        {
+            // The input was already submitted or cancelled.
+            return;
+        }
+
+        let remaining = timeout.saturating_sub(self.last_input_activity.elapsed());
+        if !remaining.is_zero() {
+            Self::schedule_auth_timeout(sender, id, remaining);
+            return;
+        }
+
+        self.cancel_click_handler().await;
+        self.display_error(
+            sender,
+            "Authentication timed out",
+            "Authentication timed out waiting for input",
+        );
+    }
+
+    /// Record a login attempt by the current user in the audit log, if enabled.
+    ///
+    /// The attempt is considered failed if a reason for the failure is given.
+    async fn audit(&self, failure_reason: Option<&str>) {
+        let audit_log = if let Some(audit_log) = &self.audit_log {
+            audit_log
+        } else {
+            return;
+        };
+
+        let session = self.sess_info.as_ref().and_then(|info| {
+            if self.updates.manual_sess_mode {
+                Some(info.sess_text.to_string())
+            } else {
+                info.sess_id.as_ref().map(ToString::to_string)
+            }
+        });
+        audit_log
+            .record(&AuditRecord::new(
+                self.get_current_username(),
+                session,
+                failure_reason.map(str::to_string),
+            ))
+            .await;
+    }
+
     /// Show an error message to the user.
     fn display_error(
         &mut self,
@@ -605,14 +1528,52 @@ impl Greeter {
         display_text: &str,
         log_text: &str,
     ) {
-        self.updates.set_error(Some(display_text.to_string()));
         error!("{log_text}");
+        self.error_id = self.error_id.wrapping_add(1);
+        let id = self.error_id;
+        let text = display_text.to_string();
+
+        let batch_delay = self.config.get_appearance().error_batch_delay_ms;
+        if batch_delay == 0 {
+            self.show_error(sender, id, text);
+        } else {
+            // Wait for any errors received right after this one, so that only the last is shown.
+            sender.oneshot_command(async move {
+                sleep(Duration::from_millis(batch_delay)).await;
+                CommandMsg::ShowErr(id, text)
+            });
+        }
+    }
 
+    /// Show the error with the given ID, unless a newer error has replaced it.
+    pub(super) fn show_error(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        id: u64,
+        text: String,
+    ) {
+        if id != self.error_id {
+            return;
+        }
+        self.updates.set_error(Some(text));
+
+        let timeout = self.config.get_appearance().error_timeout_secs;
+        if timeout == 0 {
+            // The user has to dismiss the error themselves.
+            return;
+        }
         sender.oneshot_command(async move {
-            sleep(Duration::from_secs(ERROR_MSG_CLEAR_DELAY)).await;
-            CommandMsg::ClearErr
+            sleep(Duration::from_secs(timeout)).await;
+            CommandMsg::ClearErr(id)
         });
     }
+
+    /// Clear the error with the given ID, unless a newer error has replaced it.
+    pub(super) fn clear_error(&mut self, id: u64) {
+        if id == self.error_id {
+            self.updates.set_error(None);
+        }
+    }
 }
 
 impl Drop for Greeter {
@@ -629,3 +1590,413 @@ impl Drop for Greeter {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod InputModeDisplay {
+        use super::super::*;
+
+        #[test_case(InputMode::None => "none"; "none")]
+        #[test_case(InputMode::Secret => "secret"; "secret")]
+        #[test_case(InputMode::Visible => "visible"; "visible")]
+        #[test_case(InputMode::Info => "info"; "info")]
+        fn display(mode: InputMode) -> String {
+            mode.to_string()
+        }
+
+        #[test]
+        fn info_then_secret() {
+            // eg. touching a hardware key, followed by entering its PIN
+            let modes: Vec<_> = [AuthMessageType::Info, AuthMessageType::Secret]
+                .iter()
+                .map(InputMode::for_auth_message)
+                .collect();
+            assert_eq!(modes, [InputMode::Info, InputMode::Secret]);
+        }
+
+        #[test_case(AuthMessageType::Visible => InputMode::Visible; "visible")]
+        #[test_case(AuthMessageType::Error => InputMode::None; "error")]
+        fn for_auth_message(auth_message_type: AuthMessageType) -> InputMode {
+            InputMode::for_auth_message(&auth_message_type)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod LockoutTracking {
+        use std::collections::BTreeMap;
+
+        use super::super::*;
+
+        const SETTINGS: SecuritySettings = SecuritySettings {
+            max_attempts: Some(2),
+            lockout_secs: 30,
+            pam_error_map: BTreeMap::new(),
+        };
+
+        #[test]
+        fn locked_after_max_attempts() {
+            let now = Instant::now();
+            let mut lockout = Lockout::default();
+            assert_eq!(lockout.record_failure("jdoe", &SETTINGS, now), None);
+            assert_eq!(lockout.locked_until("jdoe", now), None);
+
+            let until = lockout.record_failure("jdoe", &SETTINGS, now);
+            assert_eq!(until, Some(now + Duration::from_secs(30)));
+            assert_eq!(lockout.locked_until("jdoe", now), until);
+            assert_eq!(lockout.locked_until("other", now), None);
+            assert_eq!(
+                lockout.locked_until("jdoe", now + Duration::from_secs(30)),
+                None
+            );
+        }
+
+        #[test]
+        fn user_change_keeps_lockout() {
+            let now = Instant::now();
+            let mut lockout = Lockout::default();
+            lockout.record_failure("jdoe", &SETTINGS, now);
+            lockout.reset_failures();
+            assert_eq!(lockout.record_failure("jdoe", &SETTINGS, now), None);
+
+            lockout.record_failure("jdoe", &SETTINGS, now);
+            lockout.reset_failures();
+            assert!(lockout.locked_until("jdoe", now).is_some());
+        }
+
+        #[test]
+        fn multiple_users() {
+            let now = Instant::now();
+            let mut lockout = Lockout::default();
+            lockout.record_failure("jdoe", &SETTINGS, now);
+            lockout.record_failure("jdoe", &SETTINGS, now);
+            lockout.reset_failures();
+
+            let later = now + Duration::from_secs(10);
+            lockout.record_failure("other", &SETTINGS, later);
+            lockout.record_failure("other", &SETTINGS, later);
+            assert_eq!(
+                lockout.locked_until("jdoe", later),
+                Some(now + Duration::from_secs(30))
+            );
+            assert_eq!(
+                lockout.locked_until("other", later),
+                Some(later + Duration::from_secs(30))
+            );
+        }
+
+        #[test]
+        fn disabled() {
+            let settings = SecuritySettings {
+                max_attempts: None,
+                lockout_secs: 30,
+                pam_error_map: BTreeMap::new(),
+            };
+            let now = Instant::now();
+            let mut lockout = Lockout::default();
+            for _ in 0..10 {
+                assert_eq!(lockout.record_failure("jdoe", &settings, now), None);
+            }
+        }
+
+        #[test_case(Duration::from_secs(30) => "Too many failed attempts. Try again in 30s."; "whole")]
+        #[test_case(Duration::from_millis(29_100) => "Too many failed attempts. Try again in 30s."; "rounded up")]
+        fn text(remaining: Duration) -> String {
+            lockout_text(remaining)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod PreSession {
+        use super::super::*;
+
+        fn shell(script: &str) -> Vec<String> {
+            vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+        }
+
+        #[tokio::test]
+        async fn success() {
+            let command = shell(r#"test "$REGREET_USER" = jdoe"#);
+            assert_eq!(
+                run_user_command("pre-session", &command, "jdoe").await,
+                Ok(())
+            );
+        }
+
+        #[tokio::test]
+        async fn stderr() {
+            let command = shell(r#"echo "Can't mount home of $REGREET_USER" >&2; exit 1"#);
+            assert_eq!(
+                run_user_command("pre-session", &command, "jdoe").await,
+                Err("Can't mount home of jdoe".to_string())
+            );
+        }
+
+        #[tokio::test]
+        async fn no_stderr() {
+            let command = shell("exit 2");
+            assert_eq!(
+                run_user_command("pre-session", &command, "jdoe").await,
+                Err("Pre-session command failed with exit status: 2".to_string())
+            );
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod FacePath {
+        use std::fs::{create_dir_all, remove_dir_all, write};
+        use std::os::unix::fs::symlink;
+
+        use super::super::*;
+
+        fn home(name: &str) -> PathBuf {
+            let home =
+                std::env::temp_dir().join(format!("regreet-face-{name}-{}", std::process::id()));
+            create_dir_all(&home).unwrap();
+            home
+        }
+
+        #[test]
+        fn missing() {
+            let home = home("missing");
+            assert_eq!(face_path(&home), None);
+            remove_dir_all(&home).unwrap();
+        }
+
+        #[test]
+        fn file() {
+            let home = home("file");
+            write(home.join(".face"), "").unwrap();
+            assert_eq!(face_path(&home), Some(home.join(".face")));
+            remove_dir_all(&home).unwrap();
+        }
+
+        #[test]
+        fn symlink_to_file() {
+            let home = home("symlink");
+            write(home.join("me.png"), "").unwrap();
+            symlink(home.join("me.png"), home.join(".face")).unwrap();
+            assert_eq!(face_path(&home), Some(home.join(".face")));
+            remove_dir_all(&home).unwrap();
+        }
+
+        #[test]
+        fn dangling_symlink() {
+            let home = home("dangling");
+            symlink(home.join("gone.png"), home.join(".face")).unwrap();
+            assert_eq!(face_path(&home), None);
+            remove_dir_all(&home).unwrap();
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod InitialSession {
+        use super::super::*;
+
+        #[test_case("teacher" => Some("GNOME".to_string()); "configured overrides cache")]
+        #[test_case("student" => Some("Sway".to_string()); "cache without configured")]
+        #[test_case("guest" => None; "neither")]
+        fn initial_session(username: &str) -> Option<String> {
+            let config: Config = toml::from_str(
+                r#"
+                [[user_defaults]]
+                username = "teacher"
+                default_session = "GNOME"
+                "#,
+            )
+            .unwrap();
+            let mut cache = Cache::default();
+            cache.set_last_session("teacher", "Sway");
+            cache.set_last_session("student", "Sway");
+            super::super::initial_session(&config, &mut cache, username)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod SessionTooltip {
+        use super::super::*;
+
+        #[test_case(Some("Sway") => Some("Some".to_string()); "comment")]
+        #[test_case(Some("Xfce") => None; "no comment")]
+        #[test_case(Some("GNOME") => None; "unknown session")]
+        #[test_case(None => None; "no session")]
+        fn tooltip(session: Option<&str>) -> Option<String> {
+            let session_info = |comment: Option<&str>| SessionInfo {
+                command: Vec::new(),
+                sess_type: SessionType::Wayland,
+                localized_name: None,
+                category: None,
+                comment: comment.map(String::from),
+                icon: None,
+            };
+            let sessions = SessionMap::from([
+                ("Sway".to_string(), session_info(Some("Some"))),
+                ("Xfce".to_string(), session_info(None)),
+            ]);
+            session_tooltip(&sessions, session)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod Greeting {
+        use super::super::*;
+
+        #[test_case("Welcome to {hostname}!", None => "Welcome to lab-01!"; "hostname")]
+        #[test_case("Hi {username}!", Some("jdoe") => "Hi jdoe!"; "username")]
+        #[test_case("Hi {username}!", None => "Hi {username}!"; "no user")]
+        #[test_case("Welcome back!", Some("jdoe") => "Welcome back!"; "no placeholder")]
+        fn text(greeting_msg: &str, username: Option<&str>) -> String {
+            greeting_text(greeting_msg, "lab-01", username)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod Motd {
+        use std::fs::{remove_file, write};
+
+        use super::super::*;
+
+        #[test_case("\x1b[1;32mWelcome\x1b[0m to the lab\n\n" => Some("Welcome to the lab".to_string()); "colors")]
+        #[test_case(" \n\x1b[0m\n" => None; "empty")]
+        fn read(contents: &str) -> Option<String> {
+            let path = std::env::temp_dir().join(format!("regreet-motd-{}", std::process::id()));
+            write(&path, contents).unwrap();
+            let motd = read_motd(&path);
+            remove_file(&path).unwrap();
+            motd
+        }
+
+        #[test]
+        fn missing() {
+            assert_eq!(read_motd(Path::new("/nonexistent/motd")), None);
+        }
+
+        #[test]
+        fn preview() {
+            assert_eq!(motd_preview("short"), ("short".to_string(), false));
+            let (preview, truncated) = motd_preview(&"é".repeat(MOTD_MAX_CHARS + 1));
+            assert!(truncated);
+            assert_eq!(preview.chars().count(), MOTD_MAX_CHARS + 1);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod AutoLogin {
+        use super::super::*;
+
+        #[test_case("Alice", 5 => "Logging in as Alice in 5…"; "countdown")]
+        #[test_case("jdoe", 1 => "Logging in as jdoe in 1…"; "last second")]
+        fn text(name: &str, secs: u64) -> String {
+            auto_login_text(name, secs)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod AuthStep {
+        use super::super::*;
+
+        #[test]
+        fn two_auth_messages() {
+            let step = next_auth_step(0, &AuthMessageType::Secret);
+            assert_eq!(step, 1);
+            let step = next_auth_step(step, &AuthMessageType::Visible);
+            assert_eq!(step, 2);
+        }
+
+        #[test_case(AuthMessageType::Info; "info")]
+        #[test_case(AuthMessageType::Error; "error")]
+        fn not_a_prompt(auth_message_type: AuthMessageType) {
+            assert_eq!(next_auth_step(1, &auth_message_type), 1);
+        }
+
+        #[test_case(2, None => "Step 2"; "unknown total")]
+        #[test_case(1, Some(3) => "Step 1 of 3"; "known total")]
+        fn text(step: usize, max_steps: Option<usize>) -> String {
+            auth_step_text(step, max_steps)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod InfoMessageLog {
+        use super::super::*;
+
+        #[test]
+        fn accumulated() {
+            let mut messages = InfoMessages::default();
+            messages.push("Fingerprint accepted".to_string());
+            assert_eq!(
+                messages.push("Loading profile...".to_string()),
+                "Fingerprint accepted\nLoading profile..."
+            );
+        }
+
+        #[test]
+        fn capped() {
+            let mut messages = InfoMessages::default();
+            let mut text = String::new();
+            for i in 0..=MAX_INFO_MESSAGES {
+                text = messages.push(i.to_string());
+            }
+            assert_eq!(text, "1\n2\n3\n4\n5");
+        }
+
+        #[test]
+        fn cleared() {
+            let mut messages = InfoMessages::default();
+            messages.push("Fingerprint accepted".to_string());
+            messages.clear();
+            assert_eq!(
+                messages.push("Loading profile...".to_string()),
+                "Loading profile..."
+            );
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod PasswordChange {
+        use super::super::*;
+
+        #[test_case("Password: " => ("Password:".to_string(), false); "login")]
+        #[test_case("(current) UNIX password: " => ("Current password".to_string(), true); "current")]
+        #[test_case("New password: " => ("New password".to_string(), true); "new")]
+        #[test_case(
+            "Retype new password: " => ("Confirm new password".to_string(), true);
+            "confirm"
+        )]
+        fn prompt(auth_message: &str) -> (String, bool) {
+            let mut updates = Updates::new(String::new());
+            updates.prompt_for_input(auth_message);
+            (updates.input_prompt, updates.password_change_mode)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod GreetdError {
+        use super::super::*;
+
+        #[test_case(ErrorType::AuthError, false => "Wrong password"; "auth error")]
+        #[test_case(ErrorType::Error, false => "Wrong password"; "general error")]
+        #[test_case(ErrorType::AuthError, true => "AuthError: Wrong password"; "verbose auth error")]
+        #[test_case(ErrorType::Error, true => "Error: Wrong password"; "verbose general error")]
+        fn format(error_type: ErrorType, verbose: bool) -> String {
+            format_greetd_error(&error_type, "Wrong password", verbose)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod PasswordStrength {
+        use super::super::*;
+
+        #[test_case("" => 0.0; "empty")]
+        #[test_case("abc" => 0.0; "short")]
+        #[test_case("password" => 1.0; "single kind")]
+        #[test_case("passw0rd" => 2.0; "two kinds")]
+        #[test_case("Passw0rd!" => 3.0; "all kinds")]
+        #[test_case("correcthorsebatterystaple" => 1.0; "long single kind")]
+        #[test_case("correct horse battery staple" => 3.0; "long with two kinds")]
+        #[test_case("Correct horse battery staple!" => 4.0; "long with all kinds")]
+        fn score(password: &str) -> f64 {
+            password_strength(password)
+        }
+    }
+}
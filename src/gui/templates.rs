@@ -40,11 +40,14 @@ impl WidgetTemplate for Ui {
             gtk::Picture,
 
             /// Main login box
+            #[name = "login_frame"]
             add_overlay = &gtk::Frame {
                 set_halign: gtk::Align::Center,
                 set_valign: gtk::Align::Center,
                 add_css_class: "background",
 
+                /// Grid containing the login widgets
+                #[name = "login_grid"]
                 gtk::Grid {
                     set_column_spacing: 15,
                     set_margin_bottom: 15,
@@ -54,22 +57,84 @@ impl WidgetTemplate for Ui {
                     set_row_spacing: 15,
                     set_width_request: 500,
 
-                    /// Widget to display messages to the user
-                    #[name = "message_label"]
-                    attach[0, 0, 3, 1] = &gtk::Label {
+                    /// Collection of the messages shown at the top
+                    attach[0, 0, 3, 1] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
                         set_margin_bottom: 15,
+                        set_spacing: 10,
 
-                        // Format all messages in boldface.
-                        #[wrap(Some)]
-                        set_attributes = &gtk::pango::AttrList {
-                            insert: {
-                                let mut font_desc = gtk::pango::FontDescription::new();
-                                font_desc.set_weight(gtk::pango::Weight::Bold);
-                                gtk::pango::AttrFontDesc::new(&font_desc)
+                        /// Banner shown while PAM asks the user to change their expired password
+                        #[name = "password_change_label"]
+                        gtk::Label {
+                            set_label: "Password change required",
+                            set_visible: false,
+                            add_css_class: "warning",
+                        },
+
+                        /// Message to the user along with its icon
+                        #[name = "message_box"]
+                        gtk::Box {
+                            set_spacing: 10,
+
+                            /// Icon hinting at how to authenticate (eg. with a fingerprint)
+                            #[name = "message_icon"]
+                            gtk::Image {
+                                set_icon_size: gtk::IconSize::Large,
+                                set_visible: false,
+                            },
+
+                            /// Scrollable area for long messages (eg. several info messages)
+                            gtk::ScrolledWindow {
+                                set_hscrollbar_policy: gtk::PolicyType::Never,
+                                // Roughly two lines of text
+                                set_max_content_height: 60,
+                                set_propagate_natural_height: true,
+                                set_propagate_natural_width: true,
+
+                                /// Widget to display messages to the user
+                                #[name = "message_label"]
+                                gtk::Label {
+                                    // Format all messages in boldface.
+                                    #[wrap(Some)]
+                                    set_attributes = &gtk::pango::AttrList {
+                                        insert: {
+                                            let mut font_desc =
+                                                gtk::pango::FontDescription::new();
+                                            font_desc.set_weight(gtk::pango::Weight::Bold);
+                                            gtk::pango::AttrFontDesc::new(&font_desc)
+                                        },
+                                    },
+                                },
+                            },
+                        },
+
+                        /// Progress of a multi-step authentication, shown above its input
+                        #[name = "auth_step_label"]
+                        gtk::Label {
+                            set_visible: false,
+                            add_css_class: "dim-label",
+                        },
+
+                        /// Scrollable area for long messages of the day
+                        #[name = "motd_window"]
+                        gtk::ScrolledWindow {
+                            set_hscrollbar_policy: gtk::PolicyType::Never,
+                            set_max_content_height: 150,
+                            set_propagate_natural_height: true,
+                            set_visible: false,
+
+                            /// Message of the day (eg. from `/etc/motd`)
+                            #[name = "motd_label"]
+                            gtk::Label {
+                                set_wrap: true,
+                                set_xalign: 0.0,
+                                add_css_class: "dim-label",
                             },
                         },
                     },
 
+                    /// Label for the users widget
+                    #[name = "user_label"]
                     #[template]
                     attach[0, 1, 1, 1] = &EntryLabel {
                         set_label: "User:",
@@ -88,6 +153,10 @@ impl WidgetTemplate for Ui {
                     #[name = "usernames_box"]
                     attach[1, 1, 1, 1] = &gtk::ComboBoxText { set_hexpand: true },
 
+                    /// Search entry for filtering the users, which is moved above them if enabled
+                    #[name = "user_search"]
+                    attach[1, 7, 1, 1] = &gtk::SearchEntry { set_visible: false },
+
                     /// Widget where the user enters the username
                     #[name = "username_entry"]
                     attach[1, 1, 1, 1] = &gtk::Entry { set_hexpand: true },
@@ -96,6 +165,10 @@ impl WidgetTemplate for Ui {
                     #[name = "sessions_box"]
                     attach[1, 2, 1, 1] = &gtk::ComboBoxText,
 
+                    /// Search entry for filtering the sessions, which is moved above them if enabled
+                    #[name = "session_search"]
+                    attach[1, 6, 1, 1] = &gtk::SearchEntry { set_visible: false },
+
                     /// Widget where the user enters the session
                     #[name = "session_entry"]
                     attach[1, 2, 1, 1] = &gtk::Entry,
@@ -115,6 +188,13 @@ impl WidgetTemplate for Ui {
                     #[name = "visible_entry"]
                     attach[1, 2, 1, 1] = &gtk::Entry,
 
+                    /// Spinner shown while waiting on authentication that needs no input
+                    #[name = "auth_spinner"]
+                    attach[1, 2, 1, 1] = &gtk::Spinner {
+                        set_halign: gtk::Align::Center,
+                        set_visible: false,
+                    },
+
                     /// Button to toggle manual user entry
                     #[name = "user_toggle"]
                     attach[2, 1, 1, 1] = &gtk::ToggleButton {
@@ -122,6 +202,21 @@ impl WidgetTemplate for Ui {
                         set_tooltip_text: Some("Manually enter username"),
                     },
 
+                    /// Frame to crop the picture of the selected user into a circle
+                    #[name = "avatar_frame"]
+                    attach[3, 1, 1, 1] = &gtk::Frame {
+                        set_overflow: gtk::Overflow::Hidden,
+                        set_valign: gtk::Align::Center,
+                        add_css_class: "avatar",
+
+                        /// Picture of the selected user
+                        #[name = "avatar"]
+                        gtk::Image {
+                            set_pixel_size: 45,
+                            set_icon_name: Some("avatar-default-symbolic"),
+                        },
+                    },
+
                     /// Button to toggle manual session entry
                     #[name = "sess_toggle"]
                     attach[2, 2, 1, 1] = &gtk::ToggleButton {
@@ -129,8 +224,57 @@ impl WidgetTemplate for Ui {
                         set_tooltip_text: Some("Manually enter session command"),
                     },
 
-                    /// Collection of action buttons (eg. Login)
+                    /// Indicator for the strength of a visible input (eg. when setting passwords)
+                    #[name = "password_strength"]
+                    attach[1, 3, 1, 1] = &gtk::LevelBar {
+                        set_min_value: 0.0,
+                        set_max_value: 4.0,
+                        set_mode: gtk::LevelBarMode::Discrete,
+
+                        // Replace the default offsets with ones for each strength.
+                        remove_offset_value: Some(gtk::LEVEL_BAR_OFFSET_LOW),
+                        remove_offset_value: Some(gtk::LEVEL_BAR_OFFSET_HIGH),
+                        remove_offset_value: Some(gtk::LEVEL_BAR_OFFSET_FULL),
+                        add_offset_value: ("weak", 1.0),
+                        add_offset_value: ("fair", 2.0),
+                        add_offset_value: ("good", 3.0),
+                        add_offset_value: ("strong", 4.0),
+                    },
+
+                    /// Warning shown while entering a secret with Caps Lock on
+                    #[name = "caps_lock_label"]
+                    attach[1, 3, 1, 1] = &gtk::Label {
+                        set_label: "⇪ Caps Lock is on",
+                        set_visible: false,
+                        add_css_class: "warning",
+                    },
+
+                    /// Countdown to automatically logging in the last user
+                    #[name = "auto_login_box"]
                     attach[1, 3, 2, 1] = &gtk::Box {
+                        set_spacing: 15,
+                        set_visible: false,
+
+                        #[name = "auto_login_label"]
+                        gtk::Label {
+                            set_hexpand: true,
+                            set_xalign: 0.0,
+                        },
+
+                        /// Button to cancel automatically logging in
+                        #[name = "auto_login_cancel"]
+                        gtk::Button { set_label: "Cancel" },
+                    },
+
+                    /// Countdown shown while the user is locked out after failed login attempts
+                    #[name = "lockout_label"]
+                    attach[1, 4, 1, 1] = &gtk::Label {
+                        set_visible: false,
+                        add_css_class: "dim-label",
+                    },
+
+                    /// Collection of action buttons (eg. Login)
+                    attach[1, 5, 2, 1] = &gtk::Box {
                         set_halign: gtk::Align::End,
                         set_spacing: 15,
 
@@ -169,6 +313,32 @@ impl WidgetTemplate for Ui {
                 ",
             },
 
+            /// Status widgets at the end of the top panel (eg. hostname, battery)
+            #[name = "panel_end"]
+            add_overlay = &gtk::Frame {
+                set_halign: gtk::Align::End,
+                set_valign: gtk::Align::Start,
+                set_visible: false,
+
+                add_css_class: "background",
+
+                // Make it fit cleanly onto the top right corner of the screen.
+                inline_css: "
+                    border-top-right-radius: 0px;
+                    border-top-left-radius: 0px;
+                    border-bottom-right-radius: 0px;
+                    border-top-width: 0px;
+                    border-right-width: 0px;
+                ",
+
+                #[name = "panel_end_box"]
+                gtk::Box {
+                    set_spacing: 10,
+                    set_margin_start: 10,
+                    set_margin_end: 10,
+                },
+            },
+
             /// Collection of widgets appearing at the bottom
             add_overlay = &gtk::Box {
                 set_orientation: gtk::Orientation::Vertical,
@@ -215,6 +385,14 @@ impl WidgetTemplate for Ui {
                     #[template]
                     EndButton { set_label: "Power Off" },
                 },
+
+                /// Clock widget, when placed at the bottom of the screen
+                #[name = "bottom_clock_frame"]
+                gtk::Frame {
+                    set_halign: gtk::Align::Center,
+                    set_visible: false,
+                    add_css_class: "background",
+                },
             },
         }
     }
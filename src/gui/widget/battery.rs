@@ -0,0 +1,322 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A [serde-configurable][`BatteryConfig`] battery status widget.
+
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use relm4::{gtk::prelude::*, prelude::*};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+/// Directory containing the power supplies (including batteries) of the system
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BatteryConfig {
+    /// Whether to show the battery status
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Number of seconds between reading the battery status
+    #[serde(default = "default_refresh_secs")]
+    pub refresh_secs: u64,
+}
+
+const fn default_refresh_secs() -> u64 {
+    60
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            refresh_secs: default_refresh_secs(),
+        }
+    }
+}
+
+/// Combined status of all batteries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BatteryStatus {
+    /// Charge of all batteries, as a percentage of their total capacity
+    percent: u32,
+    /// Whether any battery is charging
+    charging: bool,
+}
+
+impl BatteryStatus {
+    /// Get the name of the icon for this status.
+    fn icon_name(self) -> String {
+        // Battery icons are only available in steps of 10%.
+        let level = (self.percent + 5) / 10 * 10;
+        if self.charging {
+            format!("battery-level-{level}-charging-symbolic")
+        } else {
+            format!("battery-level-{level}-symbolic")
+        }
+    }
+}
+
+/// Charge of a single battery
+#[derive(Clone, Copy)]
+enum Charge {
+    /// Current and full charge, read from the files of the given kind ("energy" in µWh or "charge"
+    /// in µAh)
+    Amount {
+        kind: &'static str,
+        now: u64,
+        full: u64,
+    },
+    /// Charge as a percentage, for batteries that only report that
+    Percent(u32),
+}
+
+impl Charge {
+    /// Get the charge as a percentage of the full charge.
+    fn percent(self) -> u32 {
+        match self {
+            Self::Amount { now, full, .. } => amount_percent(now, full),
+            Self::Percent(percent) => percent,
+        }
+    }
+}
+
+/// Get the current charge as a percentage of the full charge.
+fn amount_percent(now: u64, full: u64) -> u32 {
+    // Batteries may report slightly more than their full charge.
+    u32::try_from(now.min(full) * 100 / full).unwrap_or(100)
+}
+
+/// Read a number from a file in a battery's directory.
+fn read_number(path: &Path) -> Option<u64> {
+    read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Read the charge of the battery in the given directory.
+///
+/// The current and full charge are preferred, since only those can be summed across batteries.
+/// Only if neither the energy nor the charge files exist is the capacity (in percent) read.
+fn read_charge(path: &Path) -> Option<Charge> {
+    for kind in ["energy", "charge"] {
+        let now = read_number(&path.join(format!("{kind}_now")));
+        let full = read_number(&path.join(format!("{kind}_full")));
+        if let (Some(now), Some(full)) = (now, full.filter(|&full| full > 0)) {
+            return Some(Charge::Amount { kind, now, full });
+        }
+    }
+
+    let capacity = match read_to_string(path.join("capacity")) {
+        Ok(capacity) => capacity,
+        Err(err) => {
+            warn!(
+                "Couldn't read capacity of battery '{}': {err}",
+                path.display()
+            );
+            return None;
+        }
+    };
+    match capacity.trim().parse::<u32>() {
+        Ok(capacity) => Some(Charge::Percent(capacity.min(100))),
+        Err(err) => {
+            warn!("Invalid capacity of battery '{}': {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Get the combined charge of all batteries, as a percentage of their total full charge.
+///
+/// If the batteries' charges can't be summed (eg. some only report percentages), then their
+/// percentages are averaged instead.
+fn total_percent(charges: &[Charge]) -> Option<u32> {
+    let first_kind = match charges.first()? {
+        Charge::Amount { kind, .. } => Some(*kind),
+        Charge::Percent(_) => None,
+    };
+    let (mut total_now, mut total_full) = (0, 0);
+    let summable = charges.iter().all(|charge| match *charge {
+        Charge::Amount { kind, now, full } if Some(kind) == first_kind => {
+            total_now += now;
+            total_full += full;
+            true
+        }
+        _ => false,
+    });
+    if summable {
+        return Some(amount_percent(total_now, total_full));
+    }
+
+    let count = u32::try_from(charges.len()).ok()?;
+    Some(charges.iter().copied().map(Charge::percent).sum::<u32>() / count)
+}
+
+/// Read the combined status of all batteries in the power supply directory, if there are any.
+fn read_batteries(power_supply_dir: &Path) -> Option<BatteryStatus> {
+    let mut charges = Vec::new();
+    let mut charging = false;
+
+    for entry in read_dir(power_supply_dir).ok()?.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with("BAT") {
+            continue;
+        }
+        let path = entry.path();
+        let Some(charge) = read_charge(&path) else {
+            continue;
+        };
+        charges.push(charge);
+        charging |=
+            read_to_string(path.join("status")).is_ok_and(|status| status.trim() == "Charging");
+    }
+
+    Some(BatteryStatus {
+        percent: total_percent(&charges)?,
+        charging,
+    })
+}
+
+#[derive(Debug)]
+pub struct Battery {
+    power_supply_dir: PathBuf,
+
+    status: Option<BatteryStatus>,
+}
+
+/// A fixed-interval command output, to read the battery status again.
+#[derive(Debug)]
+pub struct Tick;
+
+#[relm4::component(pub)]
+impl Component for Battery {
+    type Init = BatteryConfig;
+    type Input = ();
+    type Output = ();
+    type CommandOutput = Tick;
+
+    view! {
+        gtk::Box {
+            set_spacing: 5,
+            #[watch]
+            set_visible: model.status.is_some(),
+
+            gtk::Image {
+                #[watch]
+                set_icon_name: model.status.map(BatteryStatus::icon_name).as_deref(),
+            },
+
+            gtk::Label {
+                #[watch]
+                set_text: &model
+                    .status
+                    .map_or_else(String::new, |status| format!("{}%", status.percent)),
+            },
+        }
+    }
+
+    fn init(
+        BatteryConfig { refresh_secs, .. }: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let refresh = Duration::from_secs(refresh_secs.max(1));
+        sender.command(move |sender, shutdown| {
+            shutdown
+                .register(async move {
+                    loop {
+                        if sender.send(Tick).is_err() {
+                            error!("No longer updating the battery widget because `send` failed");
+                            break;
+                        }
+                        sleep(refresh).await;
+                    }
+                })
+                .drop_on_shutdown()
+        });
+
+        let model = Self {
+            power_supply_dir: PathBuf::from(POWER_SUPPLY_DIR),
+            status: None,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_cmd(&mut self, Tick: Self::CommandOutput, _: ComponentSender<Self>, _: &Self::Root) {
+        self.status = read_batteries(&self.power_supply_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test_case(0, false => "battery-level-0-symbolic"; "empty")]
+    #[test_case(84, false => "battery-level-80-symbolic"; "round down")]
+    #[test_case(85, true => "battery-level-90-charging-symbolic"; "round up charging")]
+    #[test_case(100, false => "battery-level-100-symbolic"; "full")]
+    fn icon_name(percent: u32, charging: bool) -> String {
+        BatteryStatus { percent, charging }.icon_name()
+    }
+
+    #[test]
+    fn multiple_batteries() {
+        let dir = std::env::temp_dir().join(format!("regreet-battery-{}", std::process::id()));
+        for (name, capacity, status) in [
+            ("BAT0", "90\n", "Discharging\n"),
+            ("BAT1", "40\n", "Charging\n"),
+            ("AC", "", ""),
+        ] {
+            create_dir_all(dir.join(name)).unwrap();
+            write(dir.join(name).join("capacity"), capacity).unwrap();
+            write(dir.join(name).join("status"), status).unwrap();
+        }
+
+        let status = read_batteries(&dir);
+        remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            status,
+            Some(BatteryStatus {
+                percent: 65,
+                charging: true
+            })
+        );
+    }
+
+    #[test]
+    fn unequal_batteries() {
+        let dir = std::env::temp_dir().join(format!("regreet-batteries-{}", std::process::id()));
+        // The first battery is at 80%, and the bigger second one is at 10%.
+        for (name, now, full) in [
+            ("BAT0", "40000\n", "50000\n"),
+            ("BAT1", "10000\n", "100000\n"),
+        ] {
+            create_dir_all(dir.join(name)).unwrap();
+            write(dir.join(name).join("energy_now"), now).unwrap();
+            write(dir.join(name).join("energy_full"), full).unwrap();
+            write(dir.join(name).join("capacity"), "0\n").unwrap();
+            write(dir.join(name).join("status"), "Discharging\n").unwrap();
+        }
+
+        let status = read_batteries(&dir);
+        remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            status,
+            Some(BatteryStatus {
+                percent: 33,
+                charging: false
+            })
+        );
+    }
+
+    #[test]
+    fn no_batteries() {
+        assert_eq!(read_batteries(Path::new("/nonexistent")), None);
+    }
+}
@@ -10,11 +10,11 @@ use jiff::{fmt::strtime::format, tz::TimeZone, Timestamp, Zoned};
 use relm4::{gtk::prelude::*, prelude::*};
 use serde::{
     de::{self, Visitor},
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize,
 };
-use tokio::time::sleep;
+use tokio::{process::Command, time::sleep};
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct ClockConfig {
     /// A [strftime][fmt] argument
     ///
@@ -33,12 +33,38 @@ pub struct ClockConfig {
 
     /// A timezone from the [IANA Time Zone Database](https://en.wikipedia.org/wiki/Tz_database). If the ID is invalid
     /// or [`None`], uses the system timezone.
-    #[serde(alias = "tz", deserialize_with = "parse_tz", default = "system_tz")]
+    ///
+    /// This isn't serialized, since the system timezone may not have an ID.
+    #[serde(
+        alias = "tz",
+        deserialize_with = "parse_tz",
+        default = "system_tz",
+        skip_serializing
+    )]
     pub timezone: TimeZone,
 
     /// Ask GTK to make the label this wide. This way as the text changes, the label's size can stay static.
     #[serde(default)]
     pub label_width: u32,
+
+    /// Where on the screen to place the clock
+    #[serde(default)]
+    pub position: ClockPosition,
+
+    /// Command to run when the clock is clicked, such as to open a calendar
+    #[serde(default)]
+    pub click_action: Option<Vec<String>>,
+}
+
+/// Position of the clock on the screen
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockPosition {
+    #[default]
+    TopCenter,
+    TopLeft,
+    TopRight,
+    BottomCenter,
 }
 
 fn weekday_and_24h_time() -> String {
@@ -64,6 +90,8 @@ impl Default for ClockConfig {
             resolution: half_second(),
             timezone: system_tz(),
             label_width: label_width(),
+            position: ClockPosition::default(),
+            click_action: None,
         }
     }
 }
@@ -98,6 +126,7 @@ where
 pub struct Clock {
     format: String,
     timezone: TimeZone,
+    click_action: Option<Vec<String>>,
 
     current_time: String,
 }
@@ -117,11 +146,20 @@ impl Component for Clock {
     type CommandOutput = Tick;
 
     view! {
-        gtk::Label {
-            set_width_request: label_width.min(i32::MAX as u32) as i32,
-
-            #[watch]
-            set_text: &model.current_time
+        gtk::Button {
+            // Keep looking like a plain label, and only react to clicks if there's something to run.
+            set_has_frame: false,
+            add_css_class: "flat",
+            set_can_target: model.click_action.is_some(),
+            set_focusable: model.click_action.is_some(),
+            connect_clicked => (),
+
+            gtk::Label {
+                set_width_request: label_width.min(i32::MAX as u32) as i32,
+
+                #[watch]
+                set_text: &model.current_time
+            }
         }
     }
 
@@ -131,6 +169,8 @@ impl Component for Clock {
             resolution,
             timezone,
             label_width,
+            click_action,
+            ..
         }: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
@@ -153,6 +193,7 @@ impl Component for Clock {
             current_time: String::new(),
             format,
             timezone,
+            click_action: click_action.filter(|command| !command.is_empty()),
         };
 
         let widgets = view_output!();
@@ -160,6 +201,23 @@ impl Component for Clock {
         ComponentParts { model, widgets }
     }
 
+    fn update(&mut self, (): Self::Input, _: ComponentSender<Self>, _: &Self::Root) {
+        let Some(command) = self.click_action.clone() else {
+            return;
+        };
+        info!("Running clock click action: {command:?}");
+        relm4::spawn(async move {
+            match Command::new(&command[0]).args(&command[1..]).spawn() {
+                Ok(mut child) => {
+                    if let Err(err) = child.wait().await {
+                        error!("Failed to wait for clock click action: {err}");
+                    }
+                }
+                Err(err) => error!("Failed to run clock click action: {err}"),
+            }
+        });
+    }
+
     fn update_cmd(&mut self, Tick: Self::CommandOutput, _: ComponentSender<Self>, _: &Self::Root) {
         let now = Zoned::new(Timestamp::now(), self.timezone.clone());
 
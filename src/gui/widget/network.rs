@@ -0,0 +1,325 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A [serde-configurable][`NetworkConfig`] network connectivity indicator widget.
+
+use std::ffi::CStr;
+use std::fs::{read_dir, read_to_string};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::time::Duration;
+
+use relm4::{gtk::prelude::*, prelude::*};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+/// Directory containing the network interfaces of the system
+const NET_DIR: &str = "/sys/class/net";
+
+/// Time between checks of the network state
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Name of the loopback interface, which doesn't count as being online
+const LOOPBACK: &str = "lo";
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct NetworkConfig {
+    /// Whether to show the network connectivity
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// A network interface that is up
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Interface {
+    name: String,
+    wireless: bool,
+}
+
+/// Find the first network interface that is up, if any.
+fn active_interface(net_dir: &Path) -> Option<Interface> {
+    let mut names: Vec<_> = read_dir(net_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name != LOOPBACK)
+        .collect();
+    // Keep the chosen interface stable across checks.
+    names.sort();
+
+    names.into_iter().find_map(|name| {
+        let path = net_dir.join(&name);
+        let operstate = read_to_string(path.join("operstate")).ok()?;
+        (operstate.trim() == "up").then(|| Interface {
+            wireless: path.join("wireless").exists(),
+            name,
+        })
+    })
+}
+
+/// Get the address of the network interface with the given name, preferring IPv4 over IPv6.
+fn interface_address(name: &str) -> Option<IpAddr> {
+    let mut ifaddrs = std::ptr::null_mut();
+    // SAFETY: This only fills in the pointer to a newly allocated list.
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        warn!(
+            "Couldn't get network interface addresses: {}",
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    // SAFETY: The list was just returned by `getifaddrs`, and is only freed after this.
+    let addresses = unsafe { addresses_in(ifaddrs, name) };
+    // SAFETY: The list isn't used after this.
+    unsafe { libc::freeifaddrs(ifaddrs) };
+
+    addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .or(addresses.first())
+        .copied()
+}
+
+/// Get the addresses of the network interface with the given name from a list of addresses.
+///
+/// # Safety
+///
+/// The list must be null or a valid linked list of entries (eg. as returned by `getifaddrs`),
+/// where each entry's address is null or matches the socket address type given by its family.
+unsafe fn addresses_in(ifaddrs: *const libc::ifaddrs, name: &str) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    let mut current = ifaddrs;
+    while !current.is_null() {
+        let ifaddr = &*current;
+        current = ifaddr.ifa_next;
+        if ifaddr.ifa_addr.is_null()
+            || ifaddr.ifa_name.is_null()
+            || CStr::from_ptr(ifaddr.ifa_name).to_bytes() != name.as_bytes()
+        {
+            continue;
+        }
+
+        match i32::from((*ifaddr.ifa_addr).sa_family) {
+            libc::AF_INET => {
+                let addr = &*ifaddr.ifa_addr.cast::<libc::sockaddr_in>();
+                addresses.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    addr.sin_addr.s_addr,
+                ))));
+            }
+            libc::AF_INET6 => {
+                let addr = &*ifaddr.ifa_addr.cast::<libc::sockaddr_in6>();
+                addresses.push(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)));
+            }
+            _ => {}
+        }
+    }
+    addresses
+}
+
+/// The current network connectivity, sent by the background task that checks it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkState {
+    /// The interface that is up, or `None` if offline
+    interface: Option<Interface>,
+    /// The address of the interface that is up
+    address: Option<IpAddr>,
+}
+
+impl NetworkState {
+    /// Check the current network connectivity.
+    fn read(net_dir: &Path) -> Self {
+        let interface = active_interface(net_dir);
+        let address = interface
+            .as_ref()
+            .and_then(|interface| interface_address(&interface.name));
+        Self { interface, address }
+    }
+
+    /// Get the name of the icon for this state.
+    fn icon_name(&self) -> &'static str {
+        match &self.interface {
+            None => "network-offline-symbolic",
+            Some(Interface { wireless: true, .. }) => "network-wireless-symbolic",
+            Some(Interface {
+                wireless: false, ..
+            }) => "network-wired-symbolic",
+        }
+    }
+
+    /// Get the tooltip for this state.
+    fn tooltip(&self) -> String {
+        match (&self.interface, self.address) {
+            (None, _) => "Offline".to_string(),
+            (Some(interface), None) => interface.name.clone(),
+            (Some(interface), Some(address)) => format!("{}: {address}", interface.name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Network {
+    state: NetworkState,
+}
+
+#[relm4::component(pub)]
+impl Component for Network {
+    type Init = NetworkConfig;
+    type Input = ();
+    type Output = ();
+    type CommandOutput = NetworkState;
+
+    view! {
+        gtk::Image {
+            #[watch]
+            set_icon_name: Some(model.state.icon_name()),
+            #[watch]
+            set_tooltip_text: Some(&model.state.tooltip()),
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        sender.command(move |sender, shutdown| {
+            shutdown
+                .register(async move {
+                    loop {
+                        let state = NetworkState::read(Path::new(NET_DIR));
+                        if sender.send(state).is_err() {
+                            error!("No longer updating the network widget because `send` failed");
+                            break;
+                        }
+                        sleep(REFRESH_INTERVAL).await;
+                    }
+                })
+                .drop_on_shutdown()
+        });
+
+        let model = Self {
+            state: NetworkState::default(),
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_cmd(&mut self, state: Self::CommandOutput, _: ComponentSender<Self>, _: &Self::Root) {
+        self.state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    fn state(interface: Option<(&str, bool)>, address: Option<&str>) -> NetworkState {
+        NetworkState {
+            interface: interface.map(|(name, wireless)| Interface {
+                name: name.to_string(),
+                wireless,
+            }),
+            address: address.map(|address| address.parse().unwrap()),
+        }
+    }
+
+    #[test_case(None, None => ("network-offline-symbolic", "Offline".to_string()); "offline")]
+    #[test_case(
+        Some(("eth0", false)), Some("192.168.1.2")
+        => ("network-wired-symbolic", "eth0: 192.168.1.2".to_string());
+        "wired"
+    )]
+    #[test_case(
+        Some(("wlan0", true)), None
+        => ("network-wireless-symbolic", "wlan0".to_string());
+        "wireless without address"
+    )]
+    fn icon_and_tooltip(
+        interface: Option<(&str, bool)>,
+        address: Option<&str>,
+    ) -> (&'static str, String) {
+        let state = state(interface, address);
+        (state.icon_name(), state.tooltip())
+    }
+
+    #[test]
+    fn first_active_interface() {
+        let dir = std::env::temp_dir().join(format!("regreet-net-{}", std::process::id()));
+        for (name, operstate) in [
+            ("lo", "unknown\n"),
+            ("eth0", "down\n"),
+            ("wlan0", "up\n"),
+            ("wlan1", "up\n"),
+        ] {
+            create_dir_all(dir.join(name)).unwrap();
+            write(dir.join(name).join("operstate"), operstate).unwrap();
+        }
+        create_dir_all(dir.join("wlan0").join("wireless")).unwrap();
+
+        let interface = active_interface(&dir);
+        remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            interface,
+            Some(Interface {
+                name: "wlan0".to_string(),
+                wireless: true
+            })
+        );
+    }
+
+    /// Create an entry of a list of interface addresses.
+    fn ifaddr(name: &CStr, addr: *mut libc::sockaddr, next: *mut libc::ifaddrs) -> libc::ifaddrs {
+        // SAFETY: All fields of the entry are either integers or pointers, which can be zero.
+        let mut ifaddr: libc::ifaddrs = unsafe { std::mem::zeroed() };
+        ifaddr.ifa_name = name.as_ptr().cast_mut();
+        ifaddr.ifa_addr = addr;
+        ifaddr.ifa_next = next;
+        ifaddr
+    }
+
+    #[test]
+    fn addresses_of_interface() {
+        // SAFETY: All fields of the socket addresses are integers, which can be zero.
+        let (mut v4, mut v6): (libc::sockaddr_in, libc::sockaddr_in6) =
+            unsafe { (std::mem::zeroed(), std::mem::zeroed()) };
+        v4.sin_family = libc::AF_INET as libc::sa_family_t;
+        v4.sin_addr.s_addr = u32::from(Ipv4Addr::new(192, 168, 1, 2)).to_be();
+        v6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        v6.sin6_addr.s6_addr = Ipv6Addr::LOCALHOST.octets();
+
+        let (eth0, wlan0) = (
+            CString::new("eth0").unwrap(),
+            CString::new("wlan0").unwrap(),
+        );
+        let mut last = ifaddr(&eth0, std::ptr::null_mut(), std::ptr::null_mut());
+        let mut third = ifaddr(&eth0, (&mut v4 as *mut libc::sockaddr_in).cast(), &mut last);
+        let mut second = ifaddr(
+            &wlan0,
+            (&mut v4 as *mut libc::sockaddr_in).cast(),
+            &mut third,
+        );
+        let first = ifaddr(
+            &eth0,
+            (&mut v6 as *mut libc::sockaddr_in6).cast(),
+            &mut second,
+        );
+
+        // SAFETY: The list is built above, with each address matching its family.
+        let addresses = unsafe { addresses_in(&first, "eth0") };
+        assert_eq!(
+            addresses,
+            [
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2))
+            ]
+        );
+        // SAFETY: As above.
+        assert!(unsafe { addresses_in(&first, "lo") }.is_empty());
+    }
+}
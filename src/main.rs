@@ -2,19 +2,23 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+mod audit;
 mod cache;
 mod client;
 mod config;
+mod configcheck;
+mod configgen;
 mod constants;
 mod gui;
 mod sysutil;
 mod tomlutils;
+mod watcher;
 
-use std::fs::{create_dir_all, OpenOptions};
+use std::fs::{create_dir_all, write, OpenOptions};
 use std::io::{Result as IoResult, Write};
 use std::path::{Path, PathBuf};
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use file_rotate::{compression::Compression, suffix::AppendCount, ContentLimit, FileRotate};
 use tracing::subscriber::set_global_default;
 use tracing_appender::{non_blocking, non_blocking::WorkerGuard};
@@ -22,6 +26,8 @@ use tracing_subscriber::{
     filter::LevelFilter, fmt::layer, fmt::time::OffsetTime, layer::SubscriberExt,
 };
 
+use crate::configcheck::ConfigReport;
+use crate::configgen::generate_config;
 use crate::constants::{APP_ID, CONFIG_PATH, CSS_PATH, LOG_PATH};
 use crate::gui::{Greeter, GreeterInit};
 
@@ -49,9 +55,29 @@ enum LogLevel {
     Trace,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the config file (and custom CSS) for problems, list all parsed options, then exit
+    ///
+    /// The exit code is 0 if there are no problems, 1 if there are only warnings, and 2 if there
+    /// are errors.
+    Validate,
+
+    /// Print a config with all default options (annotated with comments), then exit
+    #[command(visible_alias = "generate-sample-config")]
+    GenerateConfig {
+        /// The path to write the generated config to, instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The path to the log file
     #[arg(short = 'l', long, value_name = "PATH", default_value = LOG_PATH)]
     logs: PathBuf,
@@ -65,20 +91,45 @@ struct Args {
     verbose: bool,
 
     /// The path to the config file
-    #[arg(short, long, value_name = "PATH", default_value = CONFIG_PATH)]
+    #[arg(short, long, global = true, value_name = "PATH", default_value = CONFIG_PATH)]
     config: PathBuf,
 
     /// The path to the custom CSS stylesheet
-    #[arg(short, long, value_name = "PATH", default_value = CSS_PATH)]
+    #[arg(short, long, global = true, value_name = "PATH", default_value = CSS_PATH)]
     style: PathBuf,
 
     /// Run in demo mode
     #[arg(long)]
     demo: bool,
+
+    /// Check the config file (and custom CSS) for problems, list all parsed options, then exit
+    #[arg(long)]
+    config_check: bool,
 }
 
 fn main() {
     let args = Args::parse();
+
+    if args.config_check || matches!(args.command, Some(Command::Validate)) {
+        let report = ConfigReport::new(&args.config, &args.style);
+        report.print();
+        std::process::exit(report.exit_code());
+    }
+
+    if let Some(Command::GenerateConfig { output }) = args.command {
+        let config = generate_config();
+        match output {
+            Some(path) => {
+                if let Err(err) = write(&path, config) {
+                    eprintln!("Couldn't write config to '{}': {err}", path.display());
+                    std::process::exit(1);
+                }
+            }
+            None => print!("{config}"),
+        }
+        return;
+    }
+
     // Keep the guard alive till the end of the function, since logging depends on this.
     let _guard = init_logging(&args.logs, &args.log_level, args.verbose);
 
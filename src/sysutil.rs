@@ -4,42 +4,199 @@
 
 //! Helper for system utilities like users and sessions
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::{read, read_to_string};
+use std::ffi::CStr;
+use std::fs::{create_dir, create_dir_all, read, read_to_string, set_permissions, Permissions};
 use std::io;
 use std::ops::ControlFlow;
-use std::path::Path;
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::path::{Path, PathBuf};
 use std::str::from_utf8;
+use std::sync::{Mutex, PoisonError};
 
 use glob::glob;
-use pwd::Passwd;
 use regex::Regex;
 use shlex::Shlex;
 
 use crate::config::Config;
-use crate::constants::{LOGIN_DEFS_PATHS, LOGIN_DEFS_UID_MAX, LOGIN_DEFS_UID_MIN, SESSION_DIRS};
+use crate::constants::{
+    LOGIN_DEFS_PATHS, LOGIN_DEFS_UID_MAX, LOGIN_DEFS_UID_MIN, RUNTIME_DIR_PARENT, SESSION_DIRS,
+    SHELLS_PATH,
+};
 
 /// XDG data directory variable name (parent directory for X11/Wayland sessions)
 const XDG_DIR_ENV_VAR: &str = "XDG_DATA_DIRS";
 
-#[derive(Clone, Copy)]
+/// Runtime directory variable name
+pub const RUNTIME_DIR_ENV_VAR: &str = "XDG_RUNTIME_DIR";
+
+/// Locale variable names for messages, in decreasing order of priority
+const LOCALE_ENV_VARS: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Lock for reading the system user database, since libc's functions for it aren't thread-safe
+static USER_DB_LOCK: Mutex<()> = Mutex::new(());
+
+/// Look up a user in the system user database by their username.
+///
+/// Unlike `pwd::Passwd::from_name`, this is safe to call while the users are re-read in the
+/// background.
+pub fn passwd_from_name(username: &str) -> pwd::Result<Option<pwd::Passwd>> {
+    let _guard = USER_DB_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    pwd::Passwd::from_name(username)
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum SessionType {
     X11,
     Wayland,
+    /// A login shell, run in the terminal
+    Shell,
     Unknown,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct SessionInfo {
     pub command: Vec<String>,
     pub sess_type: SessionType,
+    /// Name of the session in the user's language from the desktop file, if any
+    pub localized_name: Option<String>,
+    /// Primary (ie. first) category of the session from the desktop file, if any
+    pub category: Option<String>,
+    /// Description of the session from the desktop file, if any
+    pub comment: Option<String>,
+    /// Icon of the session from the desktop file (either a name in the icon theme or a path), if any
+    pub icon: Option<String>,
+}
+
+/// An entry in the system user database, as read without decoding any of its fields
+#[derive(Default)]
+struct RawPasswdEntry {
+    name: Option<Vec<u8>>,
+    uid: u32,
+    gecos: Option<Vec<u8>>,
+    shell: Option<Vec<u8>>,
+}
+
+/// An entry in the system user database
+///
+/// Unlike `pwd::Passwd`, this allows the full name and shell to not be valid UTF-8.
+struct PasswdEntry {
+    name: String,
+    uid: u32,
+    gecos: Option<Vec<u8>>,
+    shell: Vec<u8>,
+}
+
+impl PasswdEntry {
+    /// Read all entries in the system user database.
+    ///
+    /// `pwd::Passwd::iter` stops at the first entry with a non-UTF-8 field, which would silently
+    /// hide all following users. Instead, this only skips entries whose username isn't UTF-8.
+    fn read_all() -> Vec<Self> {
+        let mut raw_entries = Vec::new();
+
+        // The database is iterated through shared state, so keep other threads out till it's done.
+        let guard = USER_DB_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        // SAFETY: This only rewinds the user database to its first entry.
+        unsafe { libc::setpwent() };
+        loop {
+            // SAFETY: The returned entry is fully copied below, before the next call.
+            let entry = unsafe { libc::getpwent() };
+            if entry.is_null() {
+                break;
+            }
+            // SAFETY: A non-null entry points to a valid `passwd` struct, whose string fields are
+            // either null or valid C strings.
+            let raw_entry = unsafe {
+                let entry = &*entry;
+                let copy = |field: *const libc::c_char| {
+                    (!field.is_null()).then(|| CStr::from_ptr(field).to_bytes().to_vec())
+                };
+                RawPasswdEntry {
+                    name: copy(entry.pw_name),
+                    uid: entry.pw_uid,
+                    gecos: copy(entry.pw_gecos),
+                    shell: copy(entry.pw_shell),
+                }
+            };
+            raw_entries.push(raw_entry);
+        }
+        // SAFETY: This only closes the user database.
+        unsafe { libc::endpwent() };
+        drop(guard);
+
+        raw_entries.into_iter().filter_map(Self::from_raw).collect()
+    }
+
+    /// Decode a raw entry, skipping it if its username is missing or isn't UTF-8.
+    fn from_raw(raw_entry: RawPasswdEntry) -> Option<Self> {
+        let name = match String::from_utf8(raw_entry.name?) {
+            Ok(name) => name,
+            Err(err) => {
+                warn!(
+                    "Skipping user with non-UTF-8 username '{}': {}",
+                    String::from_utf8_lossy(err.as_bytes()),
+                    err.utf8_error()
+                );
+                return None;
+            }
+        };
+        Some(Self {
+            name,
+            uid: raw_entry.uid,
+            gecos: raw_entry.gecos,
+            shell: raw_entry.shell.unwrap_or_default(),
+        })
+    }
+
+    /// Get the full name of this user from the GECOS field.
+    ///
+    /// The system username is used if the full name is missing, empty or not valid UTF-8.
+    fn full_name(&self) -> String {
+        let gecos = if let Some(gecos) = &self.gecos {
+            gecos
+        } else {
+            debug!(
+                "Found user '{}' with UID '{}' and missing full name",
+                self.name, self.uid
+            );
+            return self.name.clone();
+        };
+
+        match from_utf8(gecos) {
+            Ok("") => {
+                debug!(
+                    "Found user '{}' with UID '{}' and empty full name",
+                    self.name, self.uid
+                );
+                self.name.clone()
+            }
+            Ok(gecos) => {
+                // Only take first entry in gecos field.
+                let gecos_name_part: &str = gecos.split(',').next().unwrap_or(gecos);
+                debug!(
+                    "Found user '{}' with UID '{}' and full name: {gecos_name_part}",
+                    self.name, self.uid
+                );
+                gecos_name_part.into()
+            }
+            Err(err) => {
+                warn!(
+                    "Found user '{}' with UID '{}' and non-UTF-8 full name: {err}",
+                    self.name, self.uid
+                );
+                self.name.clone()
+            }
+        }
+    }
 }
 
 // Convenient aliases for used maps
-type UserMap = HashMap<String, String>;
-type ShellMap = HashMap<String, Vec<String>>;
-type SessionMap = HashMap<String, SessionInfo>;
+pub type UserMap = HashMap<String, String>;
+pub type ShellMap = HashMap<String, Vec<String>>;
+pub type SessionMap = HashMap<String, SessionInfo>;
 
 /// Stores info of all regular users and sessions
 pub struct SysUtil {
@@ -49,116 +206,119 @@ pub struct SysUtil {
     shells: ShellMap,
     /// Maps a session's full name to its command
     sessions: SessionMap,
+    /// Directories containing the session desktop files
+    session_dirs: Vec<PathBuf>,
 }
 
 impl SysUtil {
     pub fn new(config: &Config) -> io::Result<Self> {
-        let path = (*LOGIN_DEFS_PATHS).iter().try_for_each(|path| {
-            if let Ok(true) = AsRef::<Path>::as_ref(&path).try_exists() {
-                ControlFlow::Break(path)
-            } else {
-                ControlFlow::Continue(())
-            }
-        });
-
-        let normal_user = match path {
-            ControlFlow::Break(path) => read_to_string(path)
-                .map_err(|err| {
-                    warn!("Failed to read login.defs from '{path}', using default values: {err}")
-                })
-                .map(|text| NormalUser::parse_login_defs(&text))
-                .unwrap_or_default(),
-            ControlFlow::Continue(()) => {
-                warn!("`login.defs` file not found in these paths: {LOGIN_DEFS_PATHS:?}",);
-
-                NormalUser::default()
-            }
-        };
-
+        let normal_user = NormalUser::load();
         debug!("{normal_user:?}");
 
-        let (users, shells) = Self::init_users(normal_user)?;
+        let (users, shells) = Self::init_users(normal_user, config)?;
+        let session_dirs = session_dirs_from(env::var(XDG_DIR_ENV_VAR).ok().as_deref());
         Ok(Self {
             users,
             shells,
-            sessions: Self::init_sessions(config)?,
+            sessions: Self::init_sessions(config, &session_dirs)?,
+            session_dirs,
         })
     }
 
     /// Get the list of regular users.
     ///
-    /// These are defined as a list of users with UID between `UID_MIN` and `UID_MAX`.
-    fn init_users(normal_user: NormalUser) -> io::Result<(UserMap, ShellMap)> {
+    /// These are defined as a list of users with UID between `UID_MIN` and `UID_MAX`, excluding
+    /// those hidden in the config.
+    fn init_users(normal_user: NormalUser, config: &Config) -> io::Result<(UserMap, ShellMap)> {
+        Ok(Self::users_from_entries(
+            PasswdEntry::read_all(),
+            normal_user,
+            &config.get_users().hidden,
+        ))
+    }
+
+    /// Get the regular users among the given user database entries, excluding the hidden ones.
+    fn users_from_entries(
+        entries: Vec<PasswdEntry>,
+        normal_user: NormalUser,
+        hidden: &[String],
+    ) -> (UserMap, ShellMap) {
         let mut users = HashMap::new();
         let mut shells = HashMap::new();
 
-        for entry in Passwd::iter().filter(|entry| normal_user.is_normal_user(entry.uid)) {
+        for entry in entries
+            .into_iter()
+            .filter(|entry| normal_user.is_normal_user(entry.uid))
+            .filter(|entry| {
+                let is_hidden = hidden.contains(&entry.name);
+                if is_hidden {
+                    debug!("Skipping hidden user: {}", entry.name);
+                }
+                !is_hidden
+            })
+        {
             // Use the actual system username if the "full name" is not available.
-            let full_name = if let Some(gecos) = entry.gecos {
-                if gecos.is_empty() {
-                    debug!(
-                        "Found user '{}' with UID '{}' and empty full name",
-                        entry.name, entry.uid
-                    );
-                    entry.name.clone()
-                } else {
-                    // Only take first entry in gecos field.
-                    let gecos_name_part: &str = gecos.split(',').next().unwrap_or(&gecos);
-                    debug!(
-                        "Found user '{}' with UID '{}' and full name: {gecos_name_part}",
-                        entry.name, entry.uid
-                    );
-                    gecos_name_part.into()
+            users.insert(entry.full_name(), entry.name.clone());
+
+            let shell = match from_utf8(&entry.shell) {
+                Ok(shell) => shell,
+                Err(err) => {
+                    // Skip this user's shell, since it can't be passed to greetd.
+                    warn!("Non-UTF-8 shell of username '{}': {err}", entry.name);
+                    continue;
                 }
-            } else {
-                debug!(
-                    "Found user '{}' with UID '{}' and missing full name",
-                    entry.name, entry.uid
-                );
-                entry.name.clone()
             };
-            users.insert(full_name, entry.name.clone());
-
-            if let Some(cmd) = shlex::split(entry.shell.as_str()) {
+            if let Some(cmd) = shlex::split(shell) {
                 shells.insert(entry.name, cmd);
             } else {
                 // Skip this user, since a missing command means that we can't use it.
                 warn!(
-                    "Couldn't split shell of username '{}' into arguments: {}",
-                    entry.name, entry.shell
+                    "Couldn't split shell of username '{}' into arguments: {shell}",
+                    entry.name
                 );
             };
         }
 
-        Ok((users, shells))
+        (users, shells)
     }
 
     /// Get available X11 and Wayland sessions.
     ///
     /// These are defined as either X11 or Wayland session desktop files stored in specific
     /// directories.
-    fn init_sessions(config: &Config) -> io::Result<SessionMap> {
+    pub fn init_sessions(config: &Config, session_dirs: &[PathBuf]) -> io::Result<SessionMap> {
         let mut found_session_names = HashSet::new();
         let mut sessions = HashMap::new();
 
-        // Use the XDG spec if available, else use the one that's compiled.
-        // The XDG env var can change after compilation in some distros like NixOS.
-        let session_dirs = if let Ok(sess_parent_dirs) = env::var(XDG_DIR_ENV_VAR) {
-            debug!("Found XDG env var {XDG_DIR_ENV_VAR}: {sess_parent_dirs}");
-            match sess_parent_dirs
-                .split(':')
-                .map(|parent_dir| format!("{parent_dir}/xsessions:{parent_dir}/wayland-sessions"))
-                .reduce(|a, b| a + ":" + &b)
-            {
-                None => SESSION_DIRS.to_string(),
-                Some(dirs) => dirs,
-            }
-        } else {
-            SESSION_DIRS.to_string()
-        };
+        // The session launch command is specified as: Exec=command arg1 arg2...
+        // This is anchored to the line start, so as to not match `TryExec`.
+        let cmd_regex = Regex::new(r"(?m)^Exec=(.*)").expect("Invalid regex for session command");
+        // The binary needed by the session is specified as: TryExec=binary
+        let try_exec_regex =
+            Regex::new(r"(?m)^TryExec=(.*)").expect("Invalid regex for session binary");
+        // The session name is specified as: Name=My Session
+        let name_regex = Regex::new(r"Name=(.*)").expect("Invalid regex for session name");
+        // The session name in other languages is specified as: Name[de]=Meine Sitzung
+        let localized_name_regex = Regex::new(r"(?m)^Name\[([^\]]+)\]=(.*)")
+            .expect("Invalid regex for localized session name");
+        let locale_keys = current_locale()
+            .map(|locale| locale_keys(&locale))
+            .unwrap_or_default();
+        // The session description is specified as: Comment=My description
+        let comment_regex =
+            Regex::new(r"(?m)^Comment=(.*)").expect("Invalid regex for session comment");
+        // The session categories are specified as: Categories=Primary;Secondary;
+        let categories_regex =
+            Regex::new(r"(?m)^Categories=(.*)").expect("Invalid regex for session categories");
+        // The session icon is specified as: Icon=name-or-path
+        let icon_regex = Regex::new(r"(?m)^Icon=(.*)").expect("Invalid regex for session icon");
+
+        // Hiding could be either as Hidden=true or NoDisplay=true
+        let hidden_regex = Regex::new(r"Hidden=(.*)").expect("Invalid regex for hidden");
+        let no_display_regex = Regex::new(r"NoDisplay=(.*)").expect("Invalid regex for no display");
 
-        for sess_dir in session_dirs.split(':') {
-            let sess_dir_path = Path::new(sess_dir);
+        for sess_dir_path in session_dirs {
+            let sess_dir = sess_dir_path.display();
             let sess_parent_dir = if let Some(sess_parent_dir) = sess_dir_path.parent() {
                 sess_parent_dir
             } else {
@@ -192,9 +352,22 @@ impl SysUtil {
                 info!("Now scanning session file: {}", path.display());
 
                 let contents = read(&path)?;
-                let text = from_utf8(contents.as_slice()).unwrap_or_else(|err| {
-                    panic!("Session file '{}' is not UTF-8: {}", path.display(), err)
-                });
+                let text = String::from_utf8_lossy(contents.as_slice());
+                if let Cow::Owned(_) = text {
+                    warn!(
+                        "Session file '{}' is not UTF-8; replaced invalid characters",
+                        path.display()
+                    );
+                }
+                let text = if let Some(section) = desktop_entry_section(&text) {
+                    section
+                } else {
+                    debug!(
+                        "No [Desktop Entry] section found, skipping session file: {}",
+                        path.display()
+                    );
+                    continue;
+                };
 
                 let fname_and_type = match path.strip_prefix(sess_parent_dir) {
                     Ok(fname_and_type) => fname_and_type.to_owned(),
@@ -212,17 +385,6 @@ impl SysUtil {
                     continue;
                 };
 
-                // The session launch command is specified as: Exec=command arg1 arg2...
-                let cmd_regex =
-                    Regex::new(r"Exec=(.*)").expect("Invalid regex for session command");
-                // The session name is specified as: Name=My Session
-                let name_regex = Regex::new(r"Name=(.*)").expect("Invalid regex for session name");
-
-                // Hiding could be either as Hidden=true or NoDisplay=true
-                let hidden_regex = Regex::new(r"Hidden=(.*)").expect("Invalid regex for hidden");
-                let no_display_regex =
-                    Regex::new(r"NoDisplay=(.*)").expect("Invalid regex for no display");
-
                 let hidden: bool = if let Some(hidden_str) = hidden_regex
                     .captures(text)
                     .and_then(|capture| capture.get(1))
@@ -241,11 +403,37 @@ impl SysUtil {
                     false
                 };
 
-                if hidden | no_display {
+                let blacklisted = path.file_stem().is_some_and(|stem| {
+                    config
+                        .get_sessions()
+                        .blacklisted
+                        .iter()
+                        .any(|name| stem == name.as_str())
+                });
+                if blacklisted {
+                    debug!("Skipping blacklisted session file: {}", path.display());
+                }
+
+                if hidden | no_display | blacklisted {
                     found_session_names.insert(fname_and_type);
                     continue;
                 };
 
+                // As per the XDG spec, the session should be ignored if its binary isn't installed.
+                if let Some(try_exec) = try_exec_regex
+                    .captures(text)
+                    .and_then(|capture| capture.get(1))
+                {
+                    let try_exec = try_exec.as_str().trim();
+                    if find_executable(try_exec).is_none() {
+                        debug!(
+                            "TryExec binary '{try_exec}' not found, skipping session file: {}",
+                            path.display()
+                        );
+                        continue;
+                    }
+                }
+
                 // Parse the desktop file to get the session command.
                 let cmd = if let Some(cmd_str) =
                     cmd_regex.captures(text).and_then(|capture| capture.get(1))
@@ -307,6 +495,40 @@ impl SysUtil {
                     // session.
                     continue;
                 };
+                // Get the name of this session in the user's language, if it has one.
+                let localized_names: HashMap<_, _> = localized_name_regex
+                    .captures_iter(text)
+                    .map(|capture| (capture[1].to_string(), capture[2].trim().to_string()))
+                    .collect();
+                let localized_name = locale_keys
+                    .iter()
+                    .find_map(|key| localized_names.get(key))
+                    .filter(|name| !name.is_empty())
+                    .cloned();
+                // Get the optional description of this session.
+                let comment = comment_regex
+                    .captures(text)
+                    .and_then(|capture| capture.get(1))
+                    .map(|comment| comment.as_str().to_string());
+                // Get the optional primary category of this session.
+                let category = categories_regex
+                    .captures(text)
+                    .and_then(|capture| capture.get(1))
+                    .and_then(|categories| {
+                        categories
+                            .as_str()
+                            .split(';')
+                            .map(str::trim)
+                            .find(|category| !category.is_empty())
+                    })
+                    .map(String::from);
+                // Get the optional icon of this session.
+                let icon = icon_regex
+                    .captures(text)
+                    .and_then(|capture| capture.get(1))
+                    .map(|icon| icon.as_str().trim().to_string())
+                    .filter(|icon| !icon.is_empty());
+
                 found_session_names.insert(fname_and_type);
                 sessions.insert(
                     name.to_string(),
@@ -317,14 +539,52 @@ impl SysUtil {
                         } else {
                             SessionType::Wayland
                         },
+                        localized_name,
+                        category,
+                        comment,
+                        icon,
                     },
                 );
             }
         }
 
+        if config.get_sessions().show_shells {
+            for (name, info) in read_shells(Path::new(SHELLS_PATH)) {
+                // Don't replace any desktop sessions with the same name.
+                sessions.entry(name).or_insert(info);
+            }
+        }
+
         Ok(sessions)
     }
 
+    /// Re-read the users from the system.
+    ///
+    /// This may block, so it should be run in the background, and the users then replaced with
+    /// [`Self::set_users`].
+    pub fn read_users(config: &Config) -> io::Result<(UserMap, ShellMap)> {
+        Self::init_users(NormalUser::load(), config)
+    }
+
+    /// Replace the users and their shells with re-read ones.
+    pub fn set_users(&mut self, (users, shells): (UserMap, ShellMap)) {
+        self.users = users;
+        self.shells = shells;
+    }
+
+    /// Get the directories to re-read the sessions from.
+    ///
+    /// The sessions can then be read with [`Self::init_sessions`] in the background, and replaced
+    /// with [`Self::set_sessions`].
+    pub fn get_session_dirs(&self) -> &[PathBuf] {
+        &self.session_dirs
+    }
+
+    /// Replace the sessions with re-read ones.
+    pub fn set_sessions(&mut self, sessions: SessionMap) {
+        self.sessions = sessions;
+    }
+
     /// Get the mapping of a user's full name to their system username.
     ///
     /// If the full name is not available, their system username is used.
@@ -345,14 +605,195 @@ impl SysUtil {
     }
 }
 
+/// Get the directories containing the session desktop files.
+pub fn session_dirs() -> Vec<PathBuf> {
+    session_dirs_from(env::var(XDG_DIR_ENV_VAR).ok().as_deref())
+}
+
+/// Get the directories containing the session desktop files, given the XDG data directories.
+fn session_dirs_from(xdg_data_dirs: Option<&str>) -> Vec<PathBuf> {
+    // Use the XDG spec if available, else use the one that's compiled.
+    // The XDG env var can change after compilation in some distros like NixOS.
+    let session_dirs = if let Some(sess_parent_dirs) = xdg_data_dirs {
+        debug!("Found XDG env var {XDG_DIR_ENV_VAR}: {sess_parent_dirs}");
+        match sess_parent_dirs
+            .split(':')
+            .map(|parent_dir| format!("{parent_dir}/xsessions:{parent_dir}/wayland-sessions"))
+            .reduce(|a, b| a + ":" + &b)
+        {
+            None => SESSION_DIRS.to_string(),
+            Some(dirs) => dirs,
+        }
+    } else {
+        SESSION_DIRS.to_string()
+    };
+    session_dirs.split(':').map(PathBuf::from).collect()
+}
+
+/// Get the contents of the `[Desktop Entry]` section of a desktop file.
+///
+/// Keys in other sections (eg. `[Desktop Action ...]`) must be ignored, as per the XDG spec.
+fn desktop_entry_section(text: &str) -> Option<&str> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if let Some(start) = start {
+            if trimmed.starts_with('[') {
+                return Some(&text[start..offset]);
+            }
+        } else if trimmed == "[Desktop Entry]" {
+            start = Some(offset + line.len());
+        }
+        offset += line.len();
+    }
+    start.map(|start| &text[start..])
+}
+
+/// Get the login shells listed in the given file (like `/etc/shells`) as sessions, keyed by name.
+///
+/// Shells that aren't installed are skipped, as are shells with the same name as an earlier one
+/// (eg. `/usr/bin/bash` after `/bin/bash`).
+fn read_shells(path: &Path) -> Vec<(String, SessionInfo)> {
+    let text = match read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            warn!(
+                "Couldn't read login shells from '{}': {err}",
+                path.display()
+            );
+            return Vec::new();
+        }
+    };
+
+    let mut shells = Vec::new();
+    let mut found_names = HashSet::new();
+    for shell in text.lines().map(str::trim) {
+        if shell.is_empty() || shell.starts_with('#') {
+            continue;
+        }
+        let Some(name) = Path::new(shell).file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if find_executable(shell).is_none() {
+            debug!("Skipping missing login shell: {shell}");
+            continue;
+        }
+        if !found_names.insert(name.to_string()) {
+            continue;
+        }
+        shells.push((
+            name.to_string(),
+            SessionInfo {
+                command: vec![shell.to_string(), "--login".to_string()],
+                sess_type: SessionType::Shell,
+                localized_name: None,
+                category: None,
+                comment: None,
+                icon: Some("utilities-terminal".to_string()),
+            },
+        ));
+    }
+    shells
+}
+
+/// Get the locale used for messages, unless it's the default "C" locale.
+fn current_locale() -> Option<String> {
+    LOCALE_ENV_VARS
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .filter(|locale| locale != "C" && locale != "POSIX")
+}
+
+/// Get the keys of localized values in desktop files to look for, from the best match to the worst.
+///
+/// For a locale like `sr_RS.UTF-8@latin`, these are the locale itself, followed by `sr_RS@latin`,
+/// `sr_RS`, `sr@latin` and `sr`, since the XDG spec ignores the encoding.
+fn locale_keys(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let rest = rest.split_once('.').map_or(rest, |(rest, _)| rest);
+    let (lang, country) = match rest.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (rest, None),
+    };
+
+    let mut keys = vec![locale.to_string()];
+    if let Some(country) = country {
+        if let Some(modifier) = modifier {
+            keys.push(format!("{lang}_{country}@{modifier}"));
+        }
+        keys.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        keys.push(format!("{lang}@{modifier}"));
+    }
+    keys.push(lang.to_string());
+    keys.dedup();
+    keys
+}
+
+/// Create a user's runtime directory, if it doesn't already exist, and get its path.
+///
+/// The directory is created in the runtime directory parent (eg. as `/run/user/1000`), so that
+/// each user gets their own.
+pub fn prepare_runtime_dir(uid: u32, gid: u32) -> io::Result<PathBuf> {
+    let path = Path::new(RUNTIME_DIR_PARENT).join(uid.to_string());
+    create_runtime_dir(&path, uid, gid)?;
+    Ok(path)
+}
+
+/// Create the runtime directory at the given path for a user, if it doesn't already exist.
+///
+/// It is only accessible to the user, as required by the XDG spec.
+fn create_runtime_dir(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    if path.exists() {
+        debug!("Runtime directory already exists: {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    create_dir(path)?;
+    // Set the permissions explicitly, since the umask may have restricted them.
+    set_permissions(path, Permissions::from_mode(0o700))?;
+    chown(path, Some(uid), Some(gid))?;
+    info!("Created runtime directory: {}", path.display());
+    Ok(())
+}
+
+/// Find the path to an executable, searching through `PATH` if the name isn't a path itself.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    env::var_os("PATH").and_then(|dirs| {
+        env::split_paths(&dirs)
+            .map(|dir| dir.join(name))
+            .find(|path| is_executable(path))
+    })
+}
+
 /// A named tuple of min and max that stores UID limits for normal users.
 ///
 /// Use [`Self::parse_login_defs`] to obtain the system configuration. If the file is missing or there are
 /// parsing errors a fallback of [`Self::default`] should be used.
 #[derive(Debug, PartialEq, Eq)]
-struct NormalUser {
-    uid_min: u64,
-    uid_max: u64,
+pub struct NormalUser {
+    pub uid_min: u64,
+    pub uid_max: u64,
 }
 
 impl Default for NormalUser {
@@ -365,6 +806,31 @@ impl Default for NormalUser {
 }
 
 impl NormalUser {
+    /// Load the UID limits from the first `login.defs` file found in [`LOGIN_DEFS_PATHS`].
+    pub fn load() -> Self {
+        let path = (*LOGIN_DEFS_PATHS).iter().try_for_each(|path| {
+            if let Ok(true) = AsRef::<Path>::as_ref(&path).try_exists() {
+                ControlFlow::Break(path)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        match path {
+            ControlFlow::Break(path) => read_to_string(path)
+                .map_err(|err| {
+                    warn!("Failed to read login.defs from '{path}', using default values: {err}")
+                })
+                .map(|text| Self::parse_login_defs(&text))
+                .unwrap_or_default(),
+            ControlFlow::Continue(()) => {
+                warn!("`login.defs` file not found in these paths: {LOGIN_DEFS_PATHS:?}",);
+
+                Self::default()
+            }
+        }
+    }
+
     /// Parses the `login.defs` file content and looks for `UID_MIN` and `UID_MAX` definitions. If a definition is
     /// missing or causes parsing errors, the default values [`struct@LOGIN_DEFS_UID_MIN`] and
     /// [`struct@LOGIN_DEFS_UID_MAX`] are used.
@@ -403,6 +869,12 @@ impl NormalUser {
             }
         }
 
+        // Both limits being 0 would allow all UIDs, including those of system users.
+        if min == Some(0) && max == Some(0) {
+            min = None;
+            max = None;
+        }
+
         Self {
             uid_min: min.unwrap_or(*LOGIN_DEFS_UID_MIN),
             uid_max: max.unwrap_or(*LOGIN_DEFS_UID_MAX),
@@ -416,22 +888,28 @@ impl NormalUser {
     /// - starts with `1..9`: decimal number
     ///
     /// In case the string value is not parsable as a number the entry value is considered invalid and `None` is
-    /// returned.
+    /// returned. Since UIDs are 32-bit, numbers above [`u32::MAX`] are also considered invalid.
     fn parse_number(num: &str) -> Option<u64> {
         let num = num.trim();
-        if num == "0" {
-            return Some(0);
-        }
-
-        if let Some(octal) = num.strip_prefix('0') {
+        let parsed = if num == "0" {
+            Some(0)
+        } else if let Some(octal) = num.strip_prefix('0') {
             if let Some(hex) = octal.strip_prefix('x') {
-                return u64::from_str_radix(hex, 16).ok();
+                u64::from_str_radix(hex, 16).ok()
+            } else {
+                u64::from_str_radix(octal, 8).ok()
             }
+        } else {
+            num.parse().ok()
+        };
 
-            return u64::from_str_radix(octal, 8).ok();
-        }
-
-        num.parse().ok()
+        parsed.filter(|&parsed| {
+            let is_valid = parsed <= u64::from(u32::MAX);
+            if !is_valid {
+                warn!("UID limit {parsed} in `login.defs` is larger than the maximum UID; ignoring it");
+            }
+            is_valid
+        })
     }
 
     // Returns true for regular users, false for those outside the UID limit, eg. git or root.
@@ -484,6 +962,11 @@ mod tests {
             => NormalUser::default();
             "invalid field (with suffix)"
         )]
+        #[test_case(
+            &["UID_MIN 0", "UID_MAX 0"].join("\n")
+            => NormalUser::default();
+            "both zero"
+        )]
         fn parse_login_defs(text: &str) -> NormalUser {
             NormalUser::parse_login_defs(text)
         }
@@ -495,8 +978,314 @@ mod tests {
         #[test_case("10" => Some(10); "decimal")]
         #[test_case("0777" => Some(0o777); "octal")]
         #[test_case("0xDeadBeef" => Some(0xdead_beef); "hex")]
+        #[test_case("4294967295" => Some(u64::from(u32::MAX)); "largest UID")]
+        #[test_case("4294967296" => None; "larger than largest UID")]
         fn parse_number(num: &str) -> Option<u64> {
             NormalUser::parse_number(num)
         }
     }
+
+    #[allow(non_snake_case)]
+    mod FullName {
+        use super::super::*;
+
+        #[test_case(None => "jdoe"; "missing")]
+        #[test_case(Some(b"") => "jdoe"; "empty")]
+        #[test_case(Some(b"John Doe") => "John Doe"; "full name")]
+        #[test_case(Some(b"John Doe,Room 1,123") => "John Doe"; "extra fields")]
+        #[test_case(Some(b"\xc0\xc1") => "jdoe"; "invalid UTF-8")]
+        #[test_case(Some(b"Jo\xc0hn,\xc1") => "jdoe"; "partially invalid UTF-8")]
+        fn full_name(gecos: Option<&[u8]>) -> String {
+            PasswdEntry {
+                name: "jdoe".to_string(),
+                uid: 1000,
+                gecos: gecos.map(<[u8]>::to_vec),
+                shell: b"/bin/sh".to_vec(),
+            }
+            .full_name()
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod RawEntry {
+        use super::super::*;
+
+        #[test]
+        fn decoded() {
+            let entry = PasswdEntry::from_raw(RawPasswdEntry {
+                name: Some(b"jdoe".to_vec()),
+                uid: 1000,
+                gecos: Some(b"J\xc0hn Doe".to_vec()),
+                shell: Some(b"/bin/\xc1sh".to_vec()),
+            })
+            .expect("Entry was skipped");
+            assert_eq!(entry.name, "jdoe");
+            assert_eq!(entry.uid, 1000);
+            // Non-UTF-8 fields other than the username are kept as they are.
+            assert_eq!(entry.gecos.as_deref(), Some(&b"J\xc0hn Doe"[..]));
+            assert_eq!(entry.shell, b"/bin/\xc1sh");
+            assert_eq!(entry.full_name(), "jdoe");
+        }
+
+        #[test]
+        fn missing_fields() {
+            let entry = PasswdEntry::from_raw(RawPasswdEntry {
+                name: Some(b"jdoe".to_vec()),
+                ..Default::default()
+            })
+            .expect("Entry was skipped");
+            assert_eq!(entry.gecos, None);
+            assert!(entry.shell.is_empty());
+        }
+
+        #[test_case(None; "missing")]
+        #[test_case(Some(b"j\xc0doe"); "invalid UTF-8")]
+        fn skipped(name: Option<&[u8]>) {
+            let entry = PasswdEntry::from_raw(RawPasswdEntry {
+                name: name.map(<[u8]>::to_vec),
+                ..Default::default()
+            });
+            assert!(entry.is_none());
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod DesktopEntry {
+        use super::super::*;
+
+        const SESSION_FILE: &str = "\
+# Comment=Wrong
+[Desktop Entry]
+Name=Correct
+Exec=correct-session
+Type=Application
+
+[X-Extension]
+Name=Wrong
+Exec=wrong-session
+";
+
+        #[test]
+        fn only_desktop_entry_keys() {
+            let section = desktop_entry_section(SESSION_FILE).expect("Section not found");
+            assert_eq!(
+                section,
+                "Name=Correct\nExec=correct-session\nType=Application\n\n"
+            );
+        }
+
+        #[test_case("[Desktop Entry]\nName=Last" => Some("Name=Last"); "last section")]
+        #[test_case("[Desktop Entry]" => Some(""); "empty section")]
+        #[test_case("Name=Orphan\nExec=orphan" => None; "no section")]
+        #[test_case("[X-Extension]\nName=Wrong" => None; "only other sections")]
+        fn section(text: &str) -> Option<&str> {
+            desktop_entry_section(text)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod RuntimeDir {
+        use std::fs::{metadata, remove_dir_all};
+
+        use super::super::*;
+
+        /// Get the IDs of the current user, since only those can be used without root.
+        fn current_ids() -> (u32, u32) {
+            // SAFETY: These functions are always successful.
+            unsafe { (libc::getuid(), libc::getgid()) }
+        }
+
+        #[test]
+        fn created() {
+            let parent = std::env::temp_dir().join(format!("regreet-test-{}", std::process::id()));
+            let (uid, gid) = current_ids();
+            let path = parent.join(uid.to_string());
+
+            create_runtime_dir(&path, uid, gid).unwrap();
+            let mode = metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+
+            // An existing directory must be left as it is.
+            set_permissions(&path, Permissions::from_mode(0o750)).unwrap();
+            create_runtime_dir(&path, uid, gid).unwrap();
+            let mode = metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
+
+            remove_dir_all(&parent).unwrap();
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod Shells {
+        use super::super::*;
+
+        #[test]
+        fn installed() {
+            let path = std::env::temp_dir().join(format!("regreet-shells-{}", std::process::id()));
+            std::fs::write(
+                &path,
+                "# Valid login shells\n/bin/sh\n\n/nonexistent/zsh\n/bin/../bin/sh\n",
+            )
+            .unwrap();
+            let shells = read_shells(&path);
+            std::fs::remove_file(&path).unwrap();
+
+            let names: Vec<_> = shells.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(names, ["sh"]);
+            assert_eq!(shells[0].1.command, ["/bin/sh", "--login"]);
+        }
+
+        #[test]
+        fn missing_file() {
+            assert!(read_shells(Path::new("/nonexistent/shells")).is_empty());
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod LocaleKeys {
+        use super::super::*;
+
+        #[test_case("de" => vec!["de"]; "language")]
+        #[test_case("de_DE" => vec!["de_DE", "de"]; "country")]
+        #[test_case("de_DE.UTF-8" => vec!["de_DE.UTF-8", "de_DE", "de"]; "encoding")]
+        #[test_case(
+            "sr_RS.UTF-8@latin" => vec!["sr_RS.UTF-8@latin", "sr_RS@latin", "sr_RS", "sr@latin", "sr"];
+            "modifier"
+        )]
+        #[test_case("sr@latin" => vec!["sr@latin", "sr"]; "modifier without country")]
+        fn keys(locale: &str) -> Vec<String> {
+            locale_keys(locale)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod InitSessions {
+        use std::fs::{create_dir_all, remove_dir_all, write};
+
+        use super::super::*;
+
+        #[test]
+        fn blacklisted() {
+            let dir = std::env::temp_dir().join(format!("regreet-sessions-{}", std::process::id()));
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(&sess_dir).unwrap();
+            write(
+                sess_dir.join("sway.desktop"),
+                "[Desktop Entry]\nName=Sway\nExec=sway\n",
+            )
+            .unwrap();
+            write(
+                sess_dir.join("sway-debug.desktop"),
+                "[Desktop Entry]\nName=Sway (Debug)\nExec=sway -d\n",
+            )
+            .unwrap();
+
+            let config: Config = toml::from_str(
+                r#"
+                [sessions]
+                blacklisted = ["sway-debug"]
+                "#,
+            )
+            .unwrap();
+            let sessions = SysUtil::init_sessions(&config, &[sess_dir]).unwrap();
+            remove_dir_all(&dir).unwrap();
+
+            assert_eq!(sessions.keys().collect::<Vec<_>>(), ["Sway"]);
+        }
+
+        #[test]
+        fn try_exec() {
+            let dir = std::env::temp_dir().join(format!("regreet-try-exec-{}", std::process::id()));
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(&sess_dir).unwrap();
+            write(
+                sess_dir.join("installed.desktop"),
+                "[Desktop Entry]\nName=Installed\nTryExec=sh\nExec=sh -l\nIcon=utilities-terminal\n",
+            )
+            .unwrap();
+            write(
+                sess_dir.join("missing.desktop"),
+                "[Desktop Entry]\nName=Missing\nTryExec=/nonexistent/compositor\nExec=compositor\n",
+            )
+            .unwrap();
+
+            let sessions = SysUtil::init_sessions(&Config::default(), &[sess_dir]).unwrap();
+            remove_dir_all(&dir).unwrap();
+
+            assert_eq!(sessions.keys().collect::<Vec<_>>(), ["Installed"]);
+            assert_eq!(sessions["Installed"].command, ["sh", "-l"]);
+            assert_eq!(
+                sessions["Installed"].icon.as_deref(),
+                Some("utilities-terminal")
+            );
+        }
+
+        #[test]
+        fn comment() {
+            let dir = std::env::temp_dir().join(format!("regreet-comment-{}", std::process::id()));
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(&sess_dir).unwrap();
+            write(
+                sess_dir.join("sway.desktop"),
+                "[Desktop Entry]\nName=Sway\n#Comment=Wrong\nX-Vendor-Comment=Wrong\n\
+                 Comment=Some\nExec=sway\n",
+            )
+            .unwrap();
+
+            let sessions = SysUtil::init_sessions(&Config::default(), &[sess_dir]).unwrap();
+            remove_dir_all(&dir).unwrap();
+
+            assert_eq!(sessions["Sway"].comment.as_deref(), Some("Some"));
+        }
+
+        #[test]
+        fn primary_category() {
+            let dir = std::env::temp_dir().join(format!("regreet-category-{}", std::process::id()));
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(&sess_dir).unwrap();
+            write(
+                sess_dir.join("sway.desktop"),
+                "[Desktop Entry]\nName=Sway\nExec=sway\nCategories=;Tiling;Wayland;\n",
+            )
+            .unwrap();
+            write(
+                sess_dir.join("plain.desktop"),
+                "[Desktop Entry]\nName=Plain\nExec=plain\n",
+            )
+            .unwrap();
+
+            let sessions = SysUtil::init_sessions(&Config::default(), &[sess_dir]).unwrap();
+            remove_dir_all(&dir).unwrap();
+
+            assert_eq!(sessions["Sway"].category.as_deref(), Some("Tiling"));
+            assert_eq!(sessions["Plain"].category, None);
+        }
+    }
+    #[allow(non_snake_case)]
+    mod InitUsers {
+        use super::super::*;
+
+        fn entry(name: &str, uid: u32) -> PasswdEntry {
+            PasswdEntry {
+                name: name.to_string(),
+                uid,
+                gecos: None,
+                shell: b"/bin/sh".to_vec(),
+            }
+        }
+
+        #[test]
+        fn hidden() {
+            let entries = vec![entry("root", 0), entry("jdoe", 1000), entry("git", 1001)];
+            let normal_user = NormalUser {
+                uid_min: 1000,
+                uid_max: 60000,
+            };
+            let (users, shells) =
+                SysUtil::users_from_entries(entries, normal_user, &["git".to_string()]);
+
+            assert_eq!(users.values().collect::<Vec<_>>(), ["jdoe"]);
+            assert_eq!(shells.keys().collect::<Vec<_>>(), ["jdoe"]);
+        }
+    }
 }
@@ -4,11 +4,18 @@
 
 //! Convenient TOML loading utilities
 
+use std::collections::HashSet;
+use std::env::VarError;
 use std::ffi::OsStr;
 use std::fs::read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use serde::de::DeserializeOwned;
+use regex::{Captures, Regex};
+use serde::{de::DeserializeOwned, Deserialize};
+use toml::{Table, Value};
+
+/// Key of the table listing the other TOML files to merge into a TOML file
+const INCLUDE_KEY: &str = "include";
 
 /// Contains possible errors when loading/saving TOML from/to disk
 #[derive(thiserror::Error, Debug)]
@@ -21,17 +28,135 @@ pub enum TomlFileError {
     TomlDecode(#[from] toml::de::Error),
     #[error("Error encoding into TOML")]
     TomlEncode(#[from] toml::ser::Error),
+    #[error("Error including TOML file '{}'", path.display())]
+    Include {
+        path: PathBuf,
+        source: Box<TomlFileError>,
+    },
 }
 
 pub type TomlFileResult<T> = Result<T, TomlFileError>;
 
 /// Load the TOML file from disk without any checks.
-fn load_raw_toml<T: DeserializeOwned>(path: &Path) -> TomlFileResult<T> {
+pub fn load_raw_toml<T: DeserializeOwned>(path: &Path) -> TomlFileResult<T> {
     Ok(toml::from_str(std::str::from_utf8(
         read(path)?.as_slice(),
     )?)?)
 }
 
+/// Other TOML files to merge into a TOML file
+#[derive(Deserialize)]
+struct Include {
+    #[serde(default)]
+    files: Vec<PathBuf>,
+}
+
+/// Recursively merge the TOML value into the base value, with the former taking priority.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Table(base), Value::Table(table)) => {
+            for (key, value) in table {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// Load the TOML file from disk, merging in the files that it includes.
+///
+/// The `ancestors` are the files that (directly or indirectly) include this one, which are skipped
+/// if included again to avoid infinite recursion. Any other included file that can't be loaded is
+/// an error.
+fn load_with_includes(path: &Path, ancestors: &mut HashSet<PathBuf>) -> TomlFileResult<Value> {
+    let mut value = Value::Table(load_raw_toml::<Table>(path)?);
+    let include = match value
+        .as_table_mut()
+        .and_then(|table| table.remove(INCLUDE_KEY))
+    {
+        Some(include) => Include::deserialize(include)?,
+        None => return Ok(value),
+    };
+
+    let canonical = path.canonicalize()?;
+    ancestors.insert(canonical.clone());
+    // Relative paths are relative to the directory of the including file.
+    let parent = path.parent().unwrap_or(Path::new(""));
+    for file in include.files {
+        let file = parent.join(file);
+        if file
+            .canonicalize()
+            .is_ok_and(|file| ancestors.contains(&file))
+        {
+            warn!("Skipping circular include of TOML file: {}", file.display());
+            continue;
+        }
+        let included =
+            load_with_includes(&file, ancestors).map_err(|err| TomlFileError::Include {
+                path: file.clone(),
+                source: Box::new(err),
+            })?;
+        info!("Included TOML file: {}", file.display());
+        merge(&mut value, included);
+    }
+    ancestors.remove(&canonical);
+
+    Ok(value)
+}
+
+/// Expand references to variables (as `$VAR` or `${VAR}`) in the text, looking them up with the
+/// given function.
+///
+/// References to variables that aren't set are left as they are, and `$$` is replaced with a
+/// literal `$`.
+fn expand_vars_with<F>(text: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Result<String, VarError>,
+{
+    let var_regex = Regex::new(r"\$(?:\$|\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))")
+        .expect("Invalid regex for environment variables");
+    var_regex
+        .replace_all(text, |captures: &Captures| {
+            let Some(name) = captures.get(1).or_else(|| captures.get(2)) else {
+                return "$".to_string();
+            };
+            let name = name.as_str();
+            lookup(name).unwrap_or_else(|err| {
+                warn!("Couldn't expand environment variable '{name}' in '{text}': {err}");
+                captures[0].to_string()
+            })
+        })
+        .into_owned()
+}
+
+/// Recursively expand references to environment variables in all strings in the TOML value.
+fn expand_env_vars_in(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = expand_vars_with(text, |name| std::env::var(name)),
+        Value::Array(array) => array.iter_mut().for_each(expand_env_vars_in),
+        Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, value)| expand_env_vars_in(value)),
+        _ => {}
+    }
+}
+
+/// Load the TOML file from disk along with the files listed in its `include.files`.
+///
+/// The included files are merged into the including file one after the other, with later files
+/// overriding the options set by earlier ones. Environment variables (as `$VAR` or `${VAR}`) in
+/// strings are then expanded.
+pub fn load_merged_toml<T: DeserializeOwned>(path: &Path) -> TomlFileResult<T> {
+    let mut value = load_with_includes(path, &mut HashSet::new())?;
+    expand_env_vars_in(&mut value);
+    Ok(value.try_into()?)
+}
+
 /// Load the TOML file from disk.
 ///
 /// If loading fails, then this returns the default value of the struct.
@@ -40,9 +165,23 @@ where
     P: AsRef<OsStr> + ?Sized,
     R: DeserializeOwned + Default,
 {
-    let path = Path::new(path);
+    load_toml_with(Path::new(path), load_raw_toml)
+}
+
+/// Load the TOML file from disk along with the files that it includes.
+///
+/// If loading fails, then this returns the default value of the struct.
+pub fn load_toml_with_includes<P, R>(path: &P) -> R
+where
+    P: AsRef<OsStr> + ?Sized,
+    R: DeserializeOwned + Default,
+{
+    load_toml_with(Path::new(path), load_merged_toml)
+}
+
+fn load_toml_with<R: Default>(path: &Path, load: fn(&Path) -> TomlFileResult<R>) -> R {
     if path.exists() {
-        match load_raw_toml(path) {
+        match load(path) {
             Ok(item) => {
                 info!("Loaded TOML file: {}", path.display());
                 item
@@ -57,3 +196,92 @@ where
         R::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test_case("$REGREET_TEST_DIR/wallpaper.jpg" => "/home/test/wallpaper.jpg"; "plain")]
+    #[test_case("${REGREET_TEST_DIR}_old" => "/home/test_old"; "braced")]
+    #[test_case("$REGREET_TEST_UNSET/log" => "$REGREET_TEST_UNSET/log"; "unset")]
+    #[test_case("${REGREET_TEST_UNSET" => "${REGREET_TEST_UNSET"; "unclosed")]
+    #[test_case("costs $5" => "costs $5"; "not a variable")]
+    #[test_case("$$REGREET_TEST_DIR/log" => "$REGREET_TEST_DIR/log"; "escaped")]
+    #[test_case("$$$REGREET_TEST_DIR" => "$/home/test"; "escaped before variable")]
+    fn expand_vars(text: &str) -> String {
+        expand_vars_with(text, |name| match name {
+            "REGREET_TEST_DIR" => Ok("/home/test".to_string()),
+            _ => Err(VarError::NotPresent),
+        })
+    }
+
+    #[test]
+    fn includes() {
+        let dir = std::env::temp_dir().join(format!("regreet-include-{}", std::process::id()));
+        create_dir_all(dir.join("regreet.d")).unwrap();
+        let main = dir.join("regreet.toml");
+        write(
+            &main,
+            r#"
+            include.files = ["regreet.d/appearance.toml", "regreet.d/sessions.toml"]
+
+            [appearance]
+            greeting_msg = "Welcome back!"
+            show_hostname = true
+            "#,
+        )
+        .unwrap();
+        write(
+            dir.join("regreet.d").join("appearance.toml"),
+            r#"
+            # Including the main file again must be skipped.
+            include.files = ["../regreet.toml"]
+
+            [appearance]
+            greeting_msg = "Hello!"
+            "#,
+        )
+        .unwrap();
+        write(
+            dir.join("regreet.d").join("sessions.toml"),
+            r#"
+            [appearance]
+            greeting_msg = "Hi!"
+
+            [sessions]
+            blacklisted = ["gnome-xorg"]
+            "#,
+        )
+        .unwrap();
+
+        let value: Value = load_merged_toml(&main).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        let expected: Value = toml::from_str(
+            r#"
+            [appearance]
+            greeting_msg = "Hi!"
+            show_hostname = true
+
+            [sessions]
+            blacklisted = ["gnome-xorg"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn missing_include() {
+        let dir = std::env::temp_dir().join(format!("regreet-missing-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let main = dir.join("regreet.toml");
+        write(&main, "include.files = [\"missing.toml\"]\n").unwrap();
+
+        let result = load_merged_toml::<Value>(&main);
+        remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(TomlFileError::Include { .. })));
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Watcher for changes to the users and sessions on the system
+//!
+//! A single inotify instance watches both the passwd file and the session directories, and the
+//! changes are sent on separate channels for users and sessions.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+use tokio::task::spawn_blocking;
+
+/// Path to the file containing the users
+const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Size of the buffer for reading inotify events
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// Receivers for notifications of changes to the users and sessions
+pub struct Changes {
+    pub users: Receiver<()>,
+    pub sessions: Receiver<()>,
+}
+
+/// The kind of path that an inotify watch is for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Watched {
+    /// The directory containing the passwd file
+    ///
+    /// The directory is watched instead of the file, since tools like `useradd` replace the file
+    /// by renaming a new one over it.
+    PasswdDir,
+    /// A directory containing session files
+    SessionDir,
+    /// The parent directory of a session directory, to notice when the latter is created
+    SessionParent,
+}
+
+struct Watcher {
+    inotify: Inotify,
+    /// Path to the file containing the users
+    passwd: PathBuf,
+    /// Directories containing the session files, which may not exist (yet)
+    session_dirs: Vec<PathBuf>,
+    /// What each inotify watch is for
+    watches: HashMap<WatchDescriptor, Watched>,
+    users: Sender<()>,
+    sessions: Sender<()>,
+}
+
+/// Start watching for changes to the users and the given session directories in the background.
+pub fn watch(session_dirs: Vec<PathBuf>) -> io::Result<Changes> {
+    watch_paths(PathBuf::from(PASSWD_PATH), session_dirs)
+}
+
+fn watch_paths(passwd: PathBuf, session_dirs: Vec<PathBuf>) -> io::Result<Changes> {
+    // Only one notification is needed to trigger a refresh, so extra ones can be dropped.
+    let (users_tx, users) = channel(1);
+    let (sessions_tx, sessions) = channel(1);
+
+    let mut watcher = Watcher {
+        inotify: Inotify::init()?,
+        passwd,
+        session_dirs,
+        watches: HashMap::new(),
+        users: users_tx,
+        sessions: sessions_tx,
+    };
+    watcher.watch_passwd()?;
+    watcher.watch_sessions();
+
+    spawn_blocking(move || watcher.run());
+    Ok(Changes { users, sessions })
+}
+
+/// Notify the receiver of a change, returning whether it is still listening.
+fn notify(sender: &Sender<()>) -> bool {
+    !matches!(sender.try_send(()), Err(TrySendError::Closed(())))
+}
+
+impl Watcher {
+    fn add_watch(&mut self, path: &Path, mask: WatchMask, kind: Watched) -> io::Result<()> {
+        let wd = self.inotify.watches().add(path, mask)?;
+        debug!("Watching {kind:?}: {}", path.display());
+        self.watches.insert(wd, kind);
+        Ok(())
+    }
+
+    fn watch_passwd(&mut self) -> io::Result<()> {
+        let passwd_dir = self
+            .passwd
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        self.add_watch(
+            &passwd_dir,
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE | WatchMask::DELETE,
+            Watched::PasswdDir,
+        )
+    }
+
+    /// (Re-)register the watches for all session directories.
+    ///
+    /// Adding a watch for an already watched path returns the same watch descriptor, so this can be
+    /// called again whenever session directories are added or removed.
+    fn watch_sessions(&mut self) {
+        self.watches.retain(|_, kind| *kind == Watched::PasswdDir);
+
+        for sess_dir in self.session_dirs.clone() {
+            if let Some(parent) = sess_dir.parent().filter(|parent| parent.is_dir()) {
+                if let Err(err) = self.add_watch(
+                    parent,
+                    WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::ONLYDIR,
+                    Watched::SessionParent,
+                ) {
+                    warn!("Couldn't watch directory '{}': {err}", parent.display());
+                }
+            }
+
+            if !sess_dir.is_dir() {
+                continue;
+            }
+            if let Err(err) = self.add_watch(
+                &sess_dir,
+                WatchMask::CLOSE_WRITE
+                    | WatchMask::CREATE
+                    | WatchMask::DELETE
+                    | WatchMask::MOVED_FROM
+                    | WatchMask::MOVED_TO
+                    | WatchMask::DELETE_SELF,
+                Watched::SessionDir,
+            ) {
+                warn!(
+                    "Couldn't watch session directory '{}': {err}",
+                    sess_dir.display()
+                );
+            }
+        }
+    }
+
+    /// Read events and send notifications until either receiver is dropped.
+    fn run(mut self) {
+        let mut buffer = [0; EVENT_BUFFER_SIZE];
+        loop {
+            let events = match self.inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(err) => {
+                    error!("Couldn't read file system events; no longer watching: {err}");
+                    return;
+                }
+            };
+
+            let mut users_changed = false;
+            let mut sessions_changed = false;
+            let mut rewatch = false;
+            for event in events {
+                match self.watches.get(&event.wd) {
+                    Some(Watched::PasswdDir) => {
+                        users_changed |= event.name == self.passwd.file_name();
+                    }
+                    Some(Watched::SessionDir) => {
+                        sessions_changed = true;
+                        // The session directory itself was removed.
+                        rewatch |= event.mask.contains(EventMask::IGNORED);
+                    }
+                    Some(Watched::SessionParent) => {
+                        let is_sess_dir = self
+                            .session_dirs
+                            .iter()
+                            .any(|sess_dir| sess_dir.file_name() == event.name);
+                        sessions_changed |= is_sess_dir;
+                        rewatch |= is_sess_dir;
+                    }
+                    None => {}
+                }
+            }
+
+            if rewatch {
+                debug!("Session directories changed; re-registering watches");
+                self.watch_sessions();
+            }
+            if users_changed && !notify(&self.users) {
+                return;
+            }
+            if sessions_changed && !notify(&self.sessions) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    /// Wait for a notification on the receiver, returning whether one was received.
+    async fn notified(receiver: &mut Receiver<()>) -> bool {
+        timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn users_and_sessions() {
+        let dir = std::env::temp_dir().join(format!("regreet-watch-{}", std::process::id()));
+        let passwd = dir.join("etc").join("passwd");
+        let sess_dir = dir.join("share").join("wayland-sessions");
+        create_dir_all(passwd.parent().unwrap()).unwrap();
+        create_dir_all(sess_dir.parent().unwrap()).unwrap();
+
+        let mut changes = watch_paths(passwd.clone(), vec![sess_dir.clone()]).unwrap();
+
+        write(&passwd, "root:x:0:0::/root:/bin/sh\n").unwrap();
+        assert!(notified(&mut changes.users).await);
+
+        // The session directory is created after the watcher started.
+        create_dir_all(&sess_dir).unwrap();
+        assert!(notified(&mut changes.sessions).await);
+        write(
+            sess_dir.join("sway.desktop"),
+            "[Desktop Entry]\nExec=sway\n",
+        )
+        .unwrap();
+        assert!(notified(&mut changes.sessions).await);
+
+        // Make the watcher notice that the receivers are gone, so that it stops.
+        drop(changes);
+        write(&passwd, "").unwrap();
+        remove_dir_all(&dir).unwrap();
+    }
+}